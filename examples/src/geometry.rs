@@ -0,0 +1,42 @@
+#[sauro::bindgen]
+mod shapes {
+    /// The length of the hypotenuse of a right triangle.
+    pub fn hypot(a: f64, b: f64) -> f64 {
+        a.hypot(b)
+    }
+}
+
+/// A module with the same name of a module in the crate root, the symbols of the bindings are
+/// unique since they include the path of the module.
+#[sauro::bindgen]
+mod units {
+    pub fn degrees_to_radians(degrees: f64) -> f64 {
+        degrees.to_radians()
+    }
+}
+
+/// A module reusing the names of other modules, the names exported by both modules are available
+/// from the namespace of each module in `mod.ts`.
+#[sauro::bindgen]
+mod space {
+    /// The length of the diagonal of a box.
+    pub fn hypot(a: f64, b: f64, c: f64) -> f64 {
+        (a * a + b * b + c * c).sqrt()
+    }
+
+    /// A counter of the points added to the space.
+    pub struct Counter {
+        points: u64,
+    }
+
+    impl Counter {
+        pub fn new() -> Self {
+            Counter { points: 0 }
+        }
+
+        pub fn add(&mut self) -> u64 {
+            self.points += 1;
+            self.points
+        }
+    }
+}
@@ -5,19 +5,269 @@ mod deno {
         b: i32,
     }
 
+    /// A point of the plane, passed by value.
+    pub struct Point {
+        /// The abscissa
+        x: f64,
+        /// The ordinate
+        y: f64,
+        id: u8,
+    }
+
+    pub struct UserId(u64);
+
+    pub struct Labeled(String, i32);
+
+    pub struct Ping;
+
+    #[serde(rename_all = "camelCase")]
+    pub struct Profile {
+        user_name: String,
+        #[serde(default)]
+        display_name: Option<String>,
+        #[serde(flatten)]
+        location: Point,
+        #[serde(skip)]
+        visits: u32,
+    }
+
+    #[serde(rename_all = "kebab-case")]
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Role {
+        /// Can change the settings of every user
+        Admin,
+        PowerUser,
+        #[serde(rename = "anonymous")]
+        Guest,
+    }
+
+    pub enum Operation {
+        Add,
+        Sub,
+        Mul,
+    }
+
+    /// A geometric shape.
+    pub enum Shape {
+        /// A circle centered in the origin
+        Circle {
+            radius: f64,
+        },
+        Rectangle {
+            width: f64,
+            height: f64,
+        },
+        Square(f64),
+        Empty,
+    }
+
+    /// A counter living in the library, shared with the caller as an opaque handle.
+    pub struct Counter {
+        value: std::cell::Cell<i64>,
+    }
+
+    impl Counter {
+        pub fn new(value: i64) -> Self {
+            let value = std::cell::Cell::new(value);
+            Counter { value }
+        }
+
+        pub fn get(&self) -> i64 {
+            self.value.get()
+        }
+
+        /// Increment the counter and return the new value.
+        pub fn increment(&mut self, by: i64) -> i64 {
+            let value = self.value.get() + by;
+            self.value.set(value);
+            value
+        }
+
+        pub fn fork(&self) -> Self {
+            Counter::new(self.value.get())
+        }
+
+        #[cfg(feature = "unstable")]
+        pub fn reset(&mut self) {
+            self.value.set(0);
+        }
+    }
+
+    pub enum ParseError {
+        Empty,
+        InvalidDigit { position: u32 },
+    }
+
     pub fn add(input: Input) -> i32 {
         input.a + input.b
     }
 
+    #[cfg(feature = "unstable")]
+    pub fn sub(a: i32, b: i32) -> i32 {
+        a - b
+    }
+
+    #[deprecated(note = "use `add` instead")]
     pub fn add2(a: i32, b: i32) -> i32 {
         a + b
     }
 
+    pub fn apply(op: Operation, a: i32, b: i32) -> i32 {
+        match op {
+            Operation::Add => a + b,
+            Operation::Sub => a - b,
+            Operation::Mul => a * b,
+        }
+    }
+
+    pub fn area(shape: Shape) -> f64 {
+        match shape {
+            Shape::Circle { radius } => std::f64::consts::PI * radius * radius,
+            Shape::Rectangle { width, height } => width * height,
+            Shape::Square(side) => side * side,
+            Shape::Empty => 0.0,
+        }
+    }
+
     #[sauro::non_blocking]
     pub fn concat(a: &str, b: &str) -> String {
         format!("{}{}", a, b)
     }
 
+    pub fn midpoint(p: Point, q: Point) -> Point {
+        Point {
+            x: (p.x + q.x) / 2.0,
+            y: (p.y + q.y) / 2.0,
+            id: p.id.max(q.id),
+        }
+    }
+
+    /// Find the largest value.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The values to compare
+    /// * `compare` - Return a negative number if the first value is smaller than the second
+    ///   one, a non negative number otherwise
+    pub fn max_by(values: &[f64], compare: impl Fn(f64, f64) -> i32) -> f64 {
+        values
+            .iter()
+            .copied()
+            .reduce(|a, b| if compare(a, b) < 0 { b } else { a })
+            .unwrap_or(f64::NAN)
+    }
+
+    pub fn count_words(text: &str, on_word: &mut dyn FnMut(&str, u32)) -> u32 {
+        let mut count = 0;
+        for word in text.split_whitespace() {
+            on_word(word, count);
+            count += 1;
+        }
+        count
+    }
+
+    pub fn visit(shapes: Vec<Shape>, visitor: &dyn Fn(Shape)) {
+        for shape in shapes {
+            visitor(shape);
+        }
+    }
+
+    pub fn call_twice(f: extern "C" fn(i32) -> i32, x: i32) -> i32 {
+        f(f(x))
+    }
+
+    pub fn greet(profile: Profile, role: Role) -> String {
+        let name = profile.display_name.unwrap_or(profile.user_name);
+        match role {
+            Role::Admin => format!("Welcome back, {}", name),
+            Role::PowerUser => format!("Hello, {}", name),
+            Role::Guest => format!("Hi, {} (visit {})", name, profile.visits + 1),
+        }
+    }
+
+    pub fn word_frequencies(text: &str) -> std::collections::BTreeMap<String, u32> {
+        let mut frequencies = std::collections::BTreeMap::new();
+        for word in text.split_whitespace() {
+            *frequencies.entry(word.to_lowercase()).or_insert(0) += 1;
+        }
+        frequencies
+    }
+
+    pub fn roles(users: std::collections::HashMap<u32, Role>) -> std::collections::BTreeSet<Role> {
+        users.into_values().collect()
+    }
+
+    pub fn distance(p: [f64; 3], q: &[f64; 3]) -> f64 {
+        p.iter()
+            .zip(q)
+            .map(|(p, q)| (p - q) * (p - q))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    pub fn checksum(data: &[u8]) -> [u8; 4] {
+        let sum = data
+            .iter()
+            .fold(0u32, |sum, byte| sum.rotate_left(5) ^ *byte as u32);
+        sum.to_be_bytes()
+    }
+
+    pub fn to_polar(point: (f64, f64)) -> (f64, f64) {
+        let (x, y) = point;
+        (x.hypot(y), y.atan2(x))
+    }
+
+    pub fn is_even(n: i64) -> bool {
+        n % 2 == 0
+    }
+
+    pub fn initial(name: &str) -> Option<char> {
+        name.chars().next()
+    }
+
+    pub fn repeat(c: char, count: u32) -> String {
+        std::iter::repeat_n(c, count as usize).collect()
+    }
+
+    pub fn factorial(n: u32) -> u128 {
+        (1..=n as u128).product()
+    }
+
+    pub fn wide_abs(x: i128) -> u128 {
+        x.unsigned_abs()
+    }
+
+    pub fn split_even(total: std::num::NonZeroU32, parts: std::num::NonZeroU32) -> u32 {
+        total.get() / parts.get()
+    }
+
+    /// The length of the message in bytes
+    pub fn log(message: &str) -> usize {
+        message.len()
+    }
+
+    /// `()` is passed and returned as `null`
+    pub fn unit(value: ()) -> () {
+        value
+    }
+
+    pub fn next_user(id: UserId) -> UserId {
+        UserId(id.0 + 1)
+    }
+
+    pub fn relabel(value: Labeled, label: &str) -> Labeled {
+        Labeled(label.to_owned(), value.1)
+    }
+
+    pub fn pong(ping: Ping) -> Ping {
+        ping
+    }
+
+    pub async fn delayed_add(a: i32, b: i32, delay_ms: u64) -> i32 {
+        crate::Delay::new(std::time::Duration::from_millis(delay_ms)).await;
+        a + b
+    }
+
     pub fn sqrt(x: f32) -> Option<f32> {
         (x > 0.0).then(|| x.sqrt())
     }
@@ -28,6 +278,28 @@ mod deno {
             .ok_or_else(|| format!("'{}' is a negative number", x))
     }
 
+    /// Parse a decimal number.
+    ///
+    /// # Errors
+    ///
+    /// The string is empty or contains a character which is not a digit.
+    pub fn parse(s: &str) -> Result<u32, ParseError> {
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        s.chars().enumerate().try_fold(0, |value, (position, c)| {
+            let position = position as u32;
+            c.to_digit(10)
+                .map(|digit| value * 10 + digit)
+                .ok_or(ParseError::InvalidDigit { position })
+        })
+    }
+
+    /// Compute `a * x + y`.
+    ///
+    /// # Panics
+    ///
+    /// The vectors `x` and `y` have different lengths.
     pub fn saxpy(a: f32, x: &[f32], y: &[f32]) -> Box<[f32]> {
         assert_eq!(x.len(), y.len());
 
@@ -37,3 +309,61 @@ mod deno {
         x.zip(y).map(|(x, y)| a * x + y).collect()
     }
 }
+
+impl std::fmt::Display for deno::ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            deno::ParseError::Empty => write!(f, "cannot parse integer from empty string"),
+            deno::ParseError::InvalidDigit { position } => {
+                write!(f, "invalid digit found at position {}", position)
+            }
+        }
+    }
+}
+
+/// A minimal timer future, woken by a background thread.
+struct Delay {
+    deadline: std::time::Instant,
+    spawned: bool,
+}
+
+impl Delay {
+    fn new(duration: std::time::Duration) -> Self {
+        let deadline = std::time::Instant::now() + duration;
+        let spawned = false;
+        Delay { deadline, spawned }
+    }
+}
+
+impl std::future::Future for Delay {
+    type Output = ();
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<()> {
+        let now = std::time::Instant::now();
+        if now >= self.deadline {
+            return std::task::Poll::Ready(());
+        }
+        if !self.spawned {
+            let waker = cx.waker().clone();
+            let duration = self.deadline - now;
+            std::thread::spawn(move || {
+                std::thread::sleep(duration);
+                waker.wake();
+            });
+            self.spawned = true;
+        }
+        std::task::Poll::Pending
+    }
+}
+
+mod geometry;
+
+#[sauro::bindgen]
+mod units {
+    pub fn celsius_to_fahrenheit(celsius: f64) -> f64 {
+        celsius * 9.0 / 5.0 + 32.0
+    }
+}
@@ -1,11 +1,17 @@
-use std::{collections::HashMap, fs::File, io::BufReader};
+use std::collections::{BTreeMap, BTreeSet};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::Parser;
-use sauro_core::syntax;
+use sha2::{Digest, Sha256};
 
-use crate::expand;
+use crate::{
+    config::{LoaderKind, PackageConfig},
+    expand,
+    manifest::{Manifest, MANIFEST_FILENAME},
+    metadata,
+    platform::{Library, Loader, Platform},
+};
 
 /// Compile the project and create the binding source code
 #[derive(Parser)]
@@ -16,9 +22,36 @@ pub struct BuildCommand {
     /// Build artifacts in release mode, with optimizations
     #[arg(long, short)]
     release: bool,
-    /// Output directory, relative to project root or absolute
-    #[arg(long, default_value_t = default_output_path())]
-    output: Utf8PathBuf,
+    /// Build artifacts with the specified profile
+    #[arg(long, value_name = "PROFILE-NAME", conflicts_with = "release")]
+    profile: Option<String>,
+    /// Package to build, can be repeated [default: the members depending on sauro]
+    #[arg(long = "package", short, value_name = "SPEC")]
+    packages: Vec<String>,
+    /// Space or comma separated list of features to activate, can be repeated
+    #[arg(long, short = 'F')]
+    features: Vec<String>,
+    /// Activate all available features
+    #[arg(long)]
+    all_features: bool,
+    /// Do not activate the `default` feature
+    #[arg(long)]
+    no_default_features: bool,
+    /// Build for the target triple, can be repeated to build for more targets
+    #[arg(long = "target", value_name = "TRIPLE")]
+    targets: Vec<String>,
+    /// Download the libraries from the base URL instead of loading them next to the bindings,
+    /// a relative URL is resolved from the output directory of the package
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+    /// Output directory, relative to the current directory or absolute [default: the `output` of
+    /// `[package.metadata.sauro]` or `bindings`]
+    #[arg(long)]
+    output: Option<Utf8PathBuf>,
+    /// Do not write the bindings, fail if they are out of date (the libraries and their checksums
+    /// are not checked)
+    #[arg(long)]
+    check: bool,
     /// Project root directory
     #[arg(default_value_t = current_dir())]
     path: Utf8PathBuf,
@@ -26,61 +59,178 @@ pub struct BuildCommand {
 
 impl BuildCommand {
     pub fn run(&self) -> Result<()> {
-        let config = {
-            let config = cargo::util::config::Config::default()?;
-            let verbosity = if self.verbose {
-                cargo::core::shell::Verbosity::Verbose
-            } else {
-                cargo::core::shell::Verbosity::Normal
-            };
-            config.shell().set_verbosity(verbosity);
-            config
+        let config = self.config()?;
+        let project = self.project(&config)?;
+        self.build(&project)?;
+
+        Ok(())
+    }
+
+    pub(super) fn config(&self) -> Result<cargo::util::config::Config> {
+        super::cargo_config(self.verbose)
+    }
+
+    pub(super) fn project<'cfg>(
+        &self,
+        config: &'cfg cargo::util::config::Config,
+    ) -> Result<Project<'cfg>> {
+        Project::new(&self.path, &self.packages, config)
+    }
+
+    /// The bindings are only checked, they are not written
+    pub(super) fn is_check(&self) -> bool {
+        self.check
+    }
+
+    /// The manifest of the project
+    pub(super) fn manifest_path(&self) -> Utf8PathBuf {
+        self.path.join("Cargo.toml")
+    }
+
+    /// Compile the packages and write their bindings
+    pub(super) fn build(&self, project: &Project) -> Result<Vec<Output>> {
+        let profile = match &self.profile {
+            Some(profile) => Some(profile.clone()),
+            None => self.release.then(|| "release".to_owned()),
         };
-        let project = Project::new(&self.path, &config)?;
         let packages = project.build(BuildOptions {
-            release: self.release,
+            profile,
+            targets: self.targets.clone(),
+            features: self.features.clone(),
+            all_features: self.all_features,
+            no_default_features: self.no_default_features,
         })?;
-        for pkg in packages {
-            pkg.expand(&self.output)?;
-        }
-
-        Ok(())
+        packages
+            .iter()
+            .map(|pkg| pkg.expand(self.output.as_deref(), self.base_url.as_deref(), self.check))
+            .collect()
     }
 }
 
+/// The bindings of a package written by a build
+pub(super) struct Output {
+    /// The directory of the bindings
+    pub(super) root: Utf8PathBuf,
+    /// The files whose content is changed by the build, relative to the root
+    pub(super) changed: Vec<Utf8PathBuf>,
+    /// The files of the previous build which are no longer generated, relative to the root
+    pub(super) removed: Vec<Utf8PathBuf>,
+}
+
 fn default_output_path() -> Utf8PathBuf {
     Utf8PathBuf::new().join("bindings")
 }
 
-fn current_dir() -> Utf8PathBuf {
+pub(super) fn current_dir() -> Utf8PathBuf {
     std::env::current_dir()
         .expect("current working directory is not valid")
         .try_into()
         .unwrap()
 }
 
-struct Project<'cfg> {
+pub(super) struct Project<'cfg> {
     config: &'cfg cargo::util::config::Config,
-    packages: Vec<cargo::core::Package>,
+    packages: Vec<(cargo::core::Package, PackageConfig)>,
 }
 
 impl<'cfg> Project<'cfg> {
-    fn new(
+    /// The selected packages, or all the members depending on sauro if none is selected
+    pub(super) fn new(
         path: impl AsRef<Utf8Path>,
+        selected: &[String],
         config: &'cfg cargo::util::config::Config,
     ) -> anyhow::Result<Self> {
         let path = path.as_ref().to_owned();
         let manifest_file = path.join("Cargo.toml");
         let workspace = cargo::core::Workspace::new(manifest_file.as_std_path(), config)?;
 
-        let packages = workspace
-            .members()
-            .filter(has_sauro_as_deps)
-            .cloned()
-            .collect::<Vec<_>>();
+        let members = if selected.is_empty() {
+            workspace.members().filter(has_sauro_as_deps).collect()
+        } else {
+            selected
+                .iter()
+                .map(|spec| {
+                    let pkg = workspace
+                        .members()
+                        .find(|pkg| pkg.name().as_str() == spec)
+                        .ok_or_else(|| anyhow!("package {} not found in the workspace", spec))?;
+                    if !has_sauro_as_deps(&pkg) {
+                        anyhow::bail!("package {} does not depend on sauro", spec);
+                    }
+                    Ok(pkg)
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        let packages = members
+            .into_iter()
+            .map(|pkg| Ok((pkg.clone(), package_config(pkg)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
         Ok(Self { config, packages })
     }
+
+    pub(super) fn packages(&self) -> impl Iterator<Item = &cargo::core::Package> {
+        self.packages.iter().map(|(pkg, _)| pkg)
+    }
+
+    /// The source files of the packages, as listed by `cargo package`
+    pub(super) fn source_files(&self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        let mut files = vec![];
+        for pkg in self.packages() {
+            let source = cargo::sources::PathSource::new(
+                pkg.root(),
+                pkg.package_id().source_id(),
+                self.config,
+            );
+            files.extend(source.list_files(pkg)?);
+        }
+        Ok(files)
+    }
+}
+
+/// The configuration in `[package.metadata.sauro]`, if any
+fn package_config(pkg: &cargo::core::Package) -> anyhow::Result<PackageConfig> {
+    let Some(value) = pkg
+        .manifest()
+        .custom_metadata()
+        .and_then(|metadata| metadata.get("sauro"))
+    else {
+        return Ok(PackageConfig::default());
+    };
+    let config = value
+        .clone()
+        .try_into::<PackageConfig>()
+        .with_context(|| format!("invalid [package.metadata.sauro] of package {}", pkg.name()))?;
+    if config.loader == LoaderKind::Remote && config.base_url.is_none() {
+        anyhow::bail!(
+            "missing base-url of the remote loader in [package.metadata.sauro] of package {}",
+            pkg.name()
+        );
+    }
+    Ok(config)
+}
+
+/// Write the file only if its content is different, `true` is returned if the file is written
+fn write_if_changed(path: &Utf8Path, content: &[u8]) -> anyhow::Result<bool> {
+    if std::fs::read(path).is_ok_and(|current| current == content) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(true)
+}
+
+/// Remove a file of the bindings, the directories left empty are removed too
+fn remove_file(root: &Utf8Path, filename: &Utf8Path) -> anyhow::Result<()> {
+    std::fs::remove_file(root.join(filename))?;
+    for dir in filename.ancestors().skip(1) {
+        if dir.as_str().is_empty() || std::fs::remove_dir(root.join(dir)).is_err() {
+            break;
+        }
+    }
+    Ok(())
 }
 
 fn has_sauro_as_deps(pkg: &&cargo::core::Package) -> bool {
@@ -90,13 +240,17 @@ fn has_sauro_as_deps(pkg: &&cargo::core::Package) -> bool {
 }
 
 struct BuildOptions {
-    release: bool,
+    profile: Option<String>,
+    targets: Vec<String>,
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
 }
 
 impl<'cfg> Project<'cfg> {
     fn build(&self, build_options: BuildOptions) -> anyhow::Result<Vec<Package>> {
         let mut packages = vec![];
-        for pkg in &self.packages {
+        for (pkg, config) in &self.packages {
             let name = pkg.name().as_str().to_owned();
             let ws = cargo::core::Workspace::new(pkg.manifest_path(), self.config)?;
 
@@ -104,34 +258,62 @@ impl<'cfg> Project<'cfg> {
                 self.config,
                 cargo::core::compiler::CompileMode::Build,
             )?;
-            if build_options.release {
-                options.build_config.requested_profile = "release".into();
+            if let Some(profile) = &build_options.profile {
+                options.build_config.requested_profile = profile.as_str().into();
+            }
+            // the features of the configuration are always enabled
+            let features = config
+                .features
+                .iter()
+                .chain(&build_options.features)
+                .cloned()
+                .collect::<Vec<_>>();
+            options.cli_features = cargo::core::resolver::CliFeatures::from_command_line(
+                &features,
+                build_options.all_features,
+                !build_options.no_default_features,
+            )?;
+            if !build_options.targets.is_empty() {
+                options.build_config.requested_kinds =
+                    cargo::core::compiler::CompileKind::from_requested_targets(
+                        self.config,
+                        &build_options.targets,
+                    )?;
             }
             let compilation = cargo::ops::compile(&ws, &options)?;
-            let dylib = compilation
+            let mut dylibs = compilation
                 .cdylibs
                 .into_iter()
-                .map(|l| l.path)
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("missing library for package {}", name))?;
-            let dylib = Utf8PathBuf::try_from(dylib)?;
-
-            let src = cargo::sources::PathSource::new(
-                pkg.root(),
-                pkg.package_id().source_id(),
-                self.config,
-            );
-            let sources = src
-                .list_files(pkg)?
-                .into_iter()
-                .filter(|p| p.extension().is_some_and(|ext| ext == "rs"))
-                .map(Utf8PathBuf::try_from)
-                .collect::<Result<_, _>>()?;
+                .map(|output| {
+                    let platform = match output.unit.kind {
+                        cargo::core::compiler::CompileKind::Host => Platform::host()?,
+                        cargo::core::compiler::CompileKind::Target(target) => {
+                            Platform::from_triple(&target.rustc_target())?
+                        }
+                    };
+                    Ok((platform, Utf8PathBuf::try_from(output.path)?))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            dylibs.sort();
+            // the bindings load a single library for each platform
+            if let Some(pair) = dylibs.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+                anyhow::bail!(
+                    "the libraries {} and {} of package {} are both built for {}",
+                    pair[0].1,
+                    pair[1].1,
+                    name,
+                    pair[0].0
+                );
+            }
+            if dylibs.is_empty() {
+                anyhow::bail!("missing library for package {}", name);
+            }
 
             packages.push(Package {
                 name,
-                sources,
-                dylib,
+                root: Utf8PathBuf::try_from(pkg.root().to_owned())?,
+                dylibs,
+                config: config.clone(),
             });
         }
         Ok(packages)
@@ -141,134 +323,214 @@ impl<'cfg> Project<'cfg> {
 #[derive(Debug)]
 struct Package {
     name: String,
-    sources: Vec<Utf8PathBuf>,
-    dylib: Utf8PathBuf,
+    /// The root directory of the package
+    root: Utf8PathBuf,
+    /// The compiled libraries, one for each platform
+    dylibs: Vec<(Platform, Utf8PathBuf)>,
+    config: PackageConfig,
 }
 
 impl Package {
-    fn expand(&self, output: &Utf8Path) -> anyhow::Result<()> {
-        let root = output.join(&self.name);
-        let dylib_filename = self.dylib.file_name().unwrap();
-        let dylib_name = Self::dylib_name(dylib_filename)?;
-        let common_prefix = path_common_prefix(&self.sources)
-            .ok_or_else(|| anyhow!("missing common prefix for sources of {} package", self.name))?;
-
-        let mut files = HashMap::new();
-        for filename_rs in &self.sources {
-            let dylib_prefix = filename_rs
-                .strip_prefix(&common_prefix)
-                .unwrap()
-                .parent()
-                .unwrap()
-                .components()
-                .fold("./".to_owned(), |p, _| format!("{}../", p));
-
-            let filename_ts = Self::typescript_filename(filename_rs, &common_prefix);
-            if let Some(source_ts) = Self::expand_source(filename_rs, &dylib_name, &dylib_prefix)? {
-                files.insert(filename_ts, source_ts);
-            }
-        }
+    fn expand(
+        &self,
+        output: Option<&Utf8Path>,
+        base_url: Option<&str>,
+        check: bool,
+    ) -> anyhow::Result<Output> {
+        // the command line options take precedence over the configuration of the package
+        let output = match (output, &self.config.output) {
+            (Some(output), _) => output.to_owned(),
+            (None, Some(output)) => self.root.join(output),
+            (None, None) => default_output_path(),
+        };
+        let root = output.join(self.config.name.as_ref().unwrap_or(&self.name));
+        let base_url = match self.config.loader {
+            _ if base_url.is_some() => base_url,
+            LoaderKind::Remote => self.config.base_url.as_deref(),
+            LoaderKind::Local => None,
+        };
+        let libraries = self
+            .dylibs
+            .iter()
+            .map(|(platform, dylib)| {
+                Ok(Library {
+                    platform: *platform,
+                    filename: dylib.file_name().unwrap().to_owned(),
+                    sha256: format!("{:x}", Sha256::digest(std::fs::read(dylib)?)),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // the library is loaded by a single file in the root of the bindings
+        let loader = match base_url {
+            Some(base_url) => Loader::Remote { base_url },
+            None => Loader::Local {
+                prefix: "./",
+                search_paths: &self.config.search_paths,
+            },
+        };
 
-        std::fs::create_dir_all(&root)?;
-        std::fs::copy(&self.dylib, root.join(dylib_filename))?;
-        for (filename, content) in files {
-            let filename = root.join(filename);
-            if let Some(parent) = filename.parent() {
-                std::fs::create_dir_all(parent)?;
+        let mut modules: Option<BTreeMap<Utf8PathBuf, expand::Bindings>> = None;
+        for (platform, dylib) in &self.dylibs {
+            let mut platform_modules = BTreeMap::new();
+            let mut module_paths = BTreeMap::new();
+            for metadata in metadata::read(dylib)? {
+                let filename_ts = self.typescript_filename(&metadata.module_path)?;
+                let module = (metadata.module_path.clone(), metadata.file.clone());
+                if let Some((other_path, other_file)) =
+                    module_paths.insert(filename_ts.clone(), module)
+                {
+                    anyhow::bail!(
+                        "the bindings of modules {} (in {}) and {} (in {}) are both expanded in {}",
+                        other_path,
+                        other_file,
+                        metadata.module_path,
+                        metadata.file,
+                        filename_ts
+                    );
+                }
+                let library_prefix = filename_ts
+                    .parent()
+                    .unwrap()
+                    .components()
+                    .fold("./".to_owned(), |p, _| format!("{}../", p));
+                let bindings = expand::expand_module(
+                    &metadata.module,
+                    &metadata.module_path,
+                    &library_prefix,
+                    self.config.integers,
+                )?;
+                platform_modules.insert(filename_ts, bindings);
             }
-            std::fs::write(filename, content)?;
-        }
-
-        Ok(())
-    }
-
-    fn dylib_name(filename: &str) -> anyhow::Result<String> {
-        let (name, _) = filename.rsplit_once('.').unwrap();
-
-        #[cfg(not(windows))]
-        let name = name.strip_prefix("lib").unwrap_or(name);
-
-        Ok(name.to_owned())
-    }
-
-    fn typescript_filename(source_rs: &Utf8Path, common_prefix: &Utf8Path) -> Utf8PathBuf {
-        let filename = source_rs
-            .strip_prefix(common_prefix)
-            .unwrap()
-            .with_extension("ts");
-        if filename.file_stem().is_some_and(|s| s == "lib") {
-            filename.with_file_name("mod.ts")
-        } else {
-            filename
-        }
-    }
 
-    fn expand_source(
-        source: &Utf8Path,
-        dylib_name: &str,
-        dylib_prefix: &str,
-    ) -> anyhow::Result<Option<String>> {
-        let ifile = File::open(source)?;
-        let content = std::io::read_to_string(BufReader::new(ifile))?;
-        let ast = syn::parse_file(&content)?;
-
-        fn has_sauro_bindgen_attr(item: &syn::ItemMod) -> bool {
-            for attr in &item.attrs {
-                if let syn::Meta::Path(path) = &attr.meta {
-                    let segments = &path.segments;
-                    if segments.len() == 2
-                        && segments[0].ident == "sauro"
-                        && segments[1].ident == "bindgen"
-                    {
-                        return true;
+            // the bindings are shared by all the platforms
+            match &modules {
+                None => modules = Some(platform_modules),
+                Some(modules) => {
+                    let is_equal = modules.len() == platform_modules.len()
+                        && modules
+                            .iter()
+                            .zip(&platform_modules)
+                            .all(|(a, b)| a.0 == b.0 && a.1.source == b.1.source);
+                    if !is_equal {
+                        anyhow::bail!(
+                            "the bindings of package {} for {} differ from the other targets",
+                            self.name,
+                            platform
+                        );
                     }
                 }
             }
-            false
+        }
+        let modules = modules.unwrap_or_default();
+        if modules.is_empty() {
+            anyhow::bail!("missing bindgen modules in package {}", self.name);
         }
 
-        let mut mods = ast
-            .items
-            .into_iter()
-            .flat_map(|item| match item {
-                syn::Item::Mod(m) if has_sauro_bindgen_attr(&m) => Some(m),
-                _ => None,
-            })
+        let mut files = modules
+            .iter()
+            .map(|(filename, bindings)| (filename.clone(), bindings.source.clone().into_bytes()))
+            .collect::<BTreeMap<_, _>>();
+        let bindings = modules.values().collect::<Vec<_>>();
+        files.insert(
+            Utf8PathBuf::from(expand::LIBRARY_FILENAME),
+            expand::expand_library(&bindings, &libraries, &loader)?.into_bytes(),
+        );
+        let entry_point = modules
+            .iter()
+            .map(|(filename, bindings)| (filename.as_str(), bindings))
             .collect::<Vec<_>>();
-
-        if mods.len() > 1 {
-            anyhow::bail!(
-                "more then one bindgen per file is not supported (file: {})",
-                source
-            );
+        files.insert(
+            Utf8PathBuf::from("mod.ts"),
+            expand::expand_entry_point(&entry_point)?.into_bytes(),
+        );
+        let mut dylibs = BTreeSet::new();
+        for (platform, dylib) in &self.dylibs {
+            let filename = Utf8Path::new(&platform.to_string()).join(dylib.file_name().unwrap());
+            files.insert(filename.clone(), std::fs::read(dylib)?);
+            dylibs.insert(filename);
         }
 
-        mods.pop()
-            .map(|item_mod| {
-                syntax::parse_module(item_mod)
-                    .map_err(anyhow::Error::from)
-                    .and_then(|module| expand::expand_module(&module, dylib_name, dylib_prefix))
-            })
-            .transpose()
-    }
-}
+        // the files of the previous build which are no longer generated are stale
+        let manifest_path = root.join(MANIFEST_FILENAME);
+        let stale =
+            Manifest::read(&manifest_path)?.stale(&root, |filename| files.contains_key(filename));
+        let manifest = Manifest {
+            files: files.keys().map(ToString::to_string).collect(),
+        };
+        let manifest = format!("{}\n", serde_json::to_string_pretty(&manifest)?);
+        files.insert(Utf8PathBuf::from(MANIFEST_FILENAME), manifest.into_bytes());
+
+        if check {
+            // the libraries are usually not committed with the bindings, their checksums are
+            // not compared either since the compilation is not always reproducible
+            let outdated = files
+                .iter()
+                .filter(|(filename, _)| !dylibs.contains(*filename))
+                .filter(|(filename, content)| {
+                    !std::fs::read(root.join(filename)).is_ok_and(|current| {
+                        if filename.as_str() == expand::LIBRARY_FILENAME {
+                            expand::without_checksums(&current)
+                                == expand::without_checksums(content)
+                        } else {
+                            current == **content
+                        }
+                    })
+                })
+                .map(|(filename, _)| filename)
+                .chain(&stale)
+                .map(|filename| root.join(filename).to_string())
+                .collect::<Vec<_>>();
+            if !outdated.is_empty() {
+                anyhow::bail!(
+                    "the bindings of package {} are out of date:\n  {}",
+                    self.name,
+                    outdated.join("\n  ")
+                );
+            }
+            return Ok(Output {
+                root,
+                changed: vec![],
+                removed: vec![],
+            });
+        }
 
-fn path_common_prefix(p: &[Utf8PathBuf]) -> Option<Utf8PathBuf> {
-    fn common_prefix_impl(p: &Utf8Path, q: &Utf8Path) -> Option<Utf8PathBuf> {
-        let p = p.components();
-        let q = q.components();
+        // the unchanged files are not written, so that a watcher of the bindings (or the cache
+        // of deno) is not invalidated without reason
+        let mut changed = vec![];
+        for (filename, content) in files {
+            if write_if_changed(&root.join(&filename), &content)? {
+                changed.push(filename);
+            }
+        }
+        for filename in &stale {
+            remove_file(&root, filename)?;
+        }
 
-        let mut components = p.zip(q).map_while(|(p, q)| (p == q).then_some(p));
-        let first = Utf8Path::to_owned(components.next()?.as_ref());
-        components
-            .fold(first, |prefix, component| prefix.join(component))
-            .into()
+        Ok(Output {
+            root,
+            changed,
+            removed: stale,
+        })
     }
 
-    let mut iter = p.iter();
-    let mut common_prefix = iter.next()?.parent().unwrap().to_owned();
-    for next in iter {
-        common_prefix = common_prefix_impl(&common_prefix, next)?;
+    /// The bindings of a module mirror its path, e.g. `my_crate::foo::bar` is expanded in
+    /// `foo/bar.ts`
+    fn typescript_filename(&self, module_path: &str) -> Result<Utf8PathBuf> {
+        let naming = self.config.file_naming;
+        let filename = module_path
+            .split("::")
+            .skip(1)
+            .map(|component| naming.apply(component))
+            .collect::<Utf8PathBuf>()
+            .with_extension("ts");
+        // the entry point and the loader are generated in the root of the bindings
+        if ["mod.ts", expand::LIBRARY_FILENAME].contains(&filename.as_str()) {
+            anyhow::bail!(
+                "the bindings of module {} can not be expanded in the reserved file {}",
+                module_path,
+                filename
+            );
+        }
+        Ok(filename)
     }
-    Some(common_prefix)
 }
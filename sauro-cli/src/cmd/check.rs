@@ -0,0 +1,91 @@
+use anyhow::{anyhow, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Parser, ValueEnum};
+
+use super::build::{current_dir, Project};
+use crate::{diagnostic::Diagnostic, source};
+
+/// Check the bindgen modules of the project, without compiling it
+#[derive(Parser)]
+pub struct CheckCommand {
+    /// Use verbose output
+    #[arg(long, short)]
+    verbose: bool,
+    /// Package to check, can be repeated [default: the members depending on sauro]
+    #[arg(long = "package", short, value_name = "SPEC")]
+    packages: Vec<String>,
+    /// Error format
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+    /// Project root directory
+    #[arg(default_value_t = current_dir())]
+    path: Utf8PathBuf,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MessageFormat {
+    /// Human readable messages, with the source code of the error
+    Human,
+    /// A JSON object for each line
+    Json,
+}
+
+impl CheckCommand {
+    pub fn run(&self) -> Result<()> {
+        let config = super::cargo_config(self.verbose)?;
+        let project = Project::new(&self.path, &self.packages, &config)?;
+
+        let mut errors = 0;
+        for pkg in project.packages() {
+            let Some(root) = pkg
+                .targets()
+                .iter()
+                .find(|target| target.is_lib())
+                .and_then(|target| target.src_path().path())
+            else {
+                continue;
+            };
+            let root = Utf8PathBuf::try_from(root.to_owned())?;
+
+            let (modules, parse_errors) = source::find_modules(&root)?;
+            let parse_errors = parse_errors
+                .into_iter()
+                .map(|parse_error| (parse_error.file, parse_error.error));
+            let module_errors = modules.into_iter().filter_map(|module| {
+                let error = sauro_core::syntax::parse_module(module.item).err()?;
+                Some((module.file, error))
+            });
+            for (file, error) in parse_errors.chain(module_errors) {
+                let file = file.strip_prefix(&self.path).unwrap_or(&file);
+                for diagnostic in Diagnostic::from_error(file, error) {
+                    self.report(&diagnostic, &self.path.join(file))?;
+                    errors += 1;
+                }
+            }
+        }
+
+        match errors {
+            0 => Ok(()),
+            1 => Err(anyhow!(
+                "could not check the bindgen modules due to a previous error"
+            )),
+            n => Err(anyhow!(
+                "could not check the bindgen modules due to {} previous errors",
+                n
+            )),
+        }
+    }
+
+    fn report(&self, diagnostic: &Diagnostic, file: &Utf8Path) -> Result<()> {
+        match self.message_format {
+            MessageFormat::Human => {
+                let source = std::fs::read_to_string(file)?;
+                let mut out = String::new();
+                diagnostic.render(&mut out, &source)?;
+                eprintln!("{}", out);
+            }
+            MessageFormat::Json => println!("{}", diagnostic.to_json()),
+        }
+        Ok(())
+    }
+}
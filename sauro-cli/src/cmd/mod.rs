@@ -1,4 +1,20 @@
 mod build;
+mod check;
 mod new;
+mod watch;
 
-pub use self::{build::BuildCommand, new::NewCommand};
+pub use self::{build::BuildCommand, check::CheckCommand, new::NewCommand, watch::WatchCommand};
+
+use anyhow::Result;
+
+/// The cargo configuration of the current environment
+fn cargo_config(verbose: bool) -> Result<cargo::util::config::Config> {
+    let config = cargo::util::config::Config::default()?;
+    let verbosity = if verbose {
+        cargo::core::shell::Verbosity::Verbose
+    } else {
+        cargo::core::shell::Verbosity::Normal
+    };
+    config.shell().set_verbosity(verbosity);
+    Ok(config)
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use super::build::{BuildCommand, Output};
+
+/// Rebuild the bindings when the source files of the project change
+#[derive(Parser)]
+pub struct WatchCommand {
+    #[command(flatten)]
+    build: BuildCommand,
+    /// Interval between two checks of the source files, in milliseconds
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 500)]
+    interval: u64,
+    /// Command restarted after each successful build, e.g. `-- deno run --allow-ffi main.ts`
+    #[arg(last = true, value_name = "COMMAND")]
+    command: Vec<String>,
+}
+
+impl WatchCommand {
+    pub fn run(&self) -> Result<()> {
+        // the bindings are never written, so they would be checked again on each change forever
+        if self.build.is_check() {
+            anyhow::bail!("the argument --check can not be used with watch");
+        }
+        let config = self.build.config()?;
+        let interval = Duration::from_millis(self.interval);
+
+        let mut child: Option<Child> = None;
+        let mut files = vec![];
+        loop {
+            // the project is loaded again on each build, the manifests could be changed
+            let build = self.build.project(&config).and_then(|project| {
+                files = project.source_files()?;
+                self.build.build(&project)
+            });
+            match build {
+                Ok(outputs) => {
+                    for output in &outputs {
+                        for filename in &output.changed {
+                            eprintln!("updated {}", output.root.join(filename));
+                        }
+                        for filename in &output.removed {
+                            eprintln!("removed {}", output.root.join(filename));
+                        }
+                    }
+                    files.retain(|file| !is_output(file, &outputs));
+                    if !self.command.is_empty() {
+                        if let Some(mut child) = child.take() {
+                            let _ = child.kill();
+                            let _ = child.wait();
+                        }
+                        // the command could be fixed before the next build, keep watching
+                        match self.spawn() {
+                            Ok(spawned) => child = Some(spawned),
+                            Err(error) => eprintln!("error: {:?}", error),
+                        }
+                    }
+                }
+                // the errors are reported and the previous bindings are kept
+                Err(error) => eprintln!("error: {:?}", error),
+            }
+            // the manifest of a virtual workspace is not a file of any package
+            let manifest_path = self.build.manifest_path().into_std_path_buf();
+            if !files.contains(&manifest_path) {
+                files.push(manifest_path);
+            }
+
+            eprintln!("watching for changes...");
+            let snapshot = modified(&files);
+            while modified(&files) == snapshot {
+                std::thread::sleep(interval);
+            }
+        }
+    }
+
+    fn spawn(&self) -> Result<Child> {
+        let (program, args) = self.command.split_first().unwrap();
+        Command::new(program)
+            .args(args)
+            .spawn()
+            .with_context(|| format!("failed to run {}", program))
+    }
+}
+
+/// The last modification time of the files, `None` if the file is missing
+fn modified(files: &[PathBuf]) -> HashMap<&PathBuf, Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| {
+            let modified = std::fs::metadata(file).and_then(|metadata| metadata.modified());
+            (file, modified.ok())
+        })
+        .collect()
+}
+
+/// The bindings could be written inside a package, they are not watched
+fn is_output(file: &Path, outputs: &[Output]) -> bool {
+    outputs.iter().any(|output| {
+        output
+            .root
+            .canonicalize()
+            .is_ok_and(|root| file.starts_with(root))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use camino::Utf8PathBuf;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn outputs_are_not_watched() {
+        let dir = TempDir::new(
+            "watch-outputs",
+            &[("src/lib.rs", ""), ("bindings/mod.ts", "")],
+        );
+        let outputs = [Output {
+            root: dir.join("bindings"),
+            changed: vec![],
+            removed: vec![],
+        }];
+        let root = dir.canonicalize().unwrap();
+        assert!(is_output(&root.join("bindings/mod.ts"), &outputs));
+        assert!(!is_output(&root.join("src/lib.rs"), &outputs));
+
+        // the missing outputs contain no files
+        let outputs = [Output {
+            root: dir.join("missing"),
+            changed: vec![],
+            removed: vec![],
+        }];
+        assert!(!is_output(&root.join("missing/mod.ts"), &outputs));
+    }
+
+    #[test]
+    fn modification_times() {
+        let dir = TempDir::new("watch-modified", &[("src/lib.rs", "")]);
+        let files =
+            [dir.join("src/lib.rs"), dir.join("src/api.rs")].map(Utf8PathBuf::into_std_path_buf);
+
+        let snapshot = modified(&files);
+        assert!(snapshot[&files[0]].is_some());
+        assert_eq!(snapshot[&files[1]], None);
+        assert_eq!(modified(&files), snapshot);
+
+        // the created files are changes
+        std::fs::write(&files[1], "").unwrap();
+        assert_ne!(modified(&files), snapshot);
+
+        let snapshot = modified(&files);
+        let time = SystemTime::now() + Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&files[0])
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+        assert_eq!(modified(&files)[&files[0]], Some(time));
+        assert_ne!(modified(&files), snapshot);
+    }
+
+    #[test]
+    fn spawn_errors_are_reported() {
+        let watch = WatchCommand::try_parse_from(["watch", "--", "sauro-missing-command"]).unwrap();
+        let error = watch.spawn().unwrap_err();
+        assert_eq!(error.to_string(), "failed to run sauro-missing-command");
+    }
+}
@@ -0,0 +1,124 @@
+//! Configuration of the bindings of a package, read from `[package.metadata.sauro]`.
+//!
+//! ```toml
+//! [package.metadata.sauro]
+//! output = "bindings"
+//! name = "my-bindings"
+//! file-naming = "kebab-case"
+//! loader = "remote"
+//! base-url = "https://example.com/releases/v1.0.0/"
+//! integers = "bigint"
+//! search-paths = ["/usr/local/lib"]
+//! features = ["ffi"]
+//! ```
+
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PackageConfig {
+    /// Output directory, relative to the package root
+    pub output: Option<String>,
+    /// Name of the directory of the bindings, the package name by default
+    pub name: Option<String>,
+    /// Naming scheme of the generated TypeScript files
+    pub file_naming: FileNaming,
+    /// How the bindings find the libraries
+    pub loader: LoaderKind,
+    /// Base URL of the libraries, required by the remote loader
+    pub base_url: Option<String>,
+    /// How the 64-bit integers are mapped to TypeScript
+    pub integers: Integers,
+    /// Directories searched for the library when it is missing next to the bindings
+    pub search_paths: Vec<String>,
+    /// Features enabled when the package is compiled
+    pub features: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum FileNaming {
+    /// The name of the Rust source file, e.g. `my_module.ts`
+    #[default]
+    #[serde(rename = "snake_case")]
+    Snake,
+    /// e.g. `my-module.ts`
+    #[serde(rename = "kebab-case")]
+    Kebab,
+    /// e.g. `myModule.ts`
+    #[serde(rename = "camelCase")]
+    Camel,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoaderKind {
+    /// The libraries are loaded from the directory of the bindings
+    #[default]
+    Local,
+    /// The libraries are downloaded from `base-url`
+    Remote,
+}
+
+/// The mapping of the 64-bit integers used by the arguments and the results of the functions.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integers {
+    /// As in Deno: a `number` when the value is a safe integer, a `bigint` otherwise
+    #[default]
+    NumberOrBigint,
+    /// Always a `bigint`
+    Bigint,
+    /// Always a `number`, the large values lose precision
+    Number,
+}
+
+impl FileNaming {
+    /// Rename a component of the path of a file, e.g. `my_module`
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            FileNaming::Snake => name.to_owned(),
+            FileNaming::Kebab => name.replace('_', "-"),
+            FileNaming::Camel => {
+                let mut camel = String::with_capacity(name.len());
+                let mut upper = false;
+                for ch in name.chars() {
+                    if ch == '_' {
+                        upper = !camel.is_empty();
+                    } else if upper {
+                        camel.extend(ch.to_uppercase());
+                        upper = false;
+                    } else {
+                        camel.push(ch);
+                    }
+                }
+                camel
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_naming() {
+        let cases = [
+            ("api", "api", "api", "api"),
+            ("my_module", "my_module", "my-module", "myModule"),
+            (
+                "my_long_module",
+                "my_long_module",
+                "my-long-module",
+                "myLongModule",
+            ),
+            ("_private", "_private", "-private", "private"),
+            ("v2_api", "v2_api", "v2-api", "v2Api"),
+        ];
+        for (name, snake, kebab, camel) in cases {
+            assert_eq!(FileNaming::Snake.apply(name), snake);
+            assert_eq!(FileNaming::Kebab.apply(name), kebab);
+            assert_eq!(FileNaming::Camel.apply(name), camel);
+        }
+    }
+}
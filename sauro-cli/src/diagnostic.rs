@@ -0,0 +1,179 @@
+//! Diagnostics reported on the bindgen modules, rendered from the spans of `syn::Error`.
+
+use camino::Utf8Path;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub message: String,
+    pub file: String,
+    /// The first line of the span, starting from 1
+    pub line: usize,
+    /// The first column of the span, starting from 1
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Diagnostic {
+    /// The diagnostics of an error, one for each combined error
+    pub fn from_error(file: &Utf8Path, error: syn::Error) -> Vec<Self> {
+        error
+            .into_iter()
+            .map(|error| {
+                let span = error.span();
+                let (start, end) = (span.start(), span.end());
+                Diagnostic {
+                    message: error.to_string(),
+                    file: file.to_string(),
+                    line: start.line,
+                    column: start.column + 1,
+                    end_line: end.line,
+                    end_column: end.column + 1,
+                }
+            })
+            .collect()
+    }
+
+    /// Render the diagnostic as the compiler does, with the source code of the span
+    pub fn render(&self, out: &mut impl std::fmt::Write, source: &str) -> std::fmt::Result {
+        writeln!(out, "error: {}", self.message)?;
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        writeln!(
+            out,
+            "{}--> {}:{}:{}",
+            gutter, self.file, self.line, self.column
+        )?;
+        let Some(code) = source.lines().nth(self.line.saturating_sub(1)) else {
+            return Ok(());
+        };
+        let len = if self.end_line == self.line {
+            self.end_column.saturating_sub(self.column).max(1)
+        } else {
+            code.chars().count().saturating_sub(self.column - 1).max(1)
+        };
+        writeln!(out, "{} |", gutter)?;
+        writeln!(out, "{} | {}", self.line, code)?;
+        writeln!(
+            out,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.column - 1),
+            "^".repeat(len)
+        )?;
+        Ok(())
+    }
+
+    /// Render the diagnostic as a single JSON line
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("diagnostics are always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(line: usize, column: usize, end_line: usize, end_column: usize) -> Diagnostic {
+        Diagnostic {
+            message: "unsupported type".to_owned(),
+            file: "src/lib.rs".to_owned(),
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+
+    #[test]
+    fn from_error() {
+        let source = "mod api {\n    pub fn f(x: i128) {}\n}\n";
+        let syn::Item::Mod(item) = &syn::parse_file(source).unwrap().items[0] else {
+            unreachable!();
+        };
+        let syn::Item::Fn(item) = &item.content.as_ref().unwrap().1[0] else {
+            unreachable!();
+        };
+        let syn::FnArg::Typed(arg) = &item.sig.inputs[0] else {
+            unreachable!();
+        };
+        let mut error = syn::Error::new_spanned(&arg.ty, "unsupported type");
+        error.combine(syn::Error::new_spanned(&item.sig.ident, "unsupported name"));
+
+        let diagnostics = Diagnostic::from_error(Utf8Path::new("src/lib.rs"), error);
+        let spans = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.message.as_str(),
+                    d.line,
+                    d.column,
+                    d.end_line,
+                    d.end_column,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                ("unsupported type", 2, 17, 2, 21),
+                ("unsupported name", 2, 12, 2, 13)
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.file == "src/lib.rs"));
+    }
+
+    #[test]
+    fn render() {
+        let source = "mod api {\n    pub fn f(x: i128) {}\n}\n";
+        let mut out = String::new();
+        diagnostic(2, 17, 2, 21).render(&mut out, source).unwrap();
+        assert_eq!(
+            out,
+            "error: unsupported type\n \
+             --> src/lib.rs:2:17\n  \
+             |\n\
+             2 |     pub fn f(x: i128) {}\n  \
+             |                 ^^^^\n"
+        );
+    }
+
+    #[test]
+    fn render_multiline_span() {
+        let source = "mod api {\n    pub fn f() {\n    }\n}\n";
+        let mut out = String::new();
+        diagnostic(2, 5, 3, 6).render(&mut out, source).unwrap();
+        assert_eq!(
+            out,
+            "error: unsupported type\n \
+             --> src/lib.rs:2:5\n  \
+             |\n\
+             2 |     pub fn f() {\n  \
+             |     ^^^^^^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn render_missing_line() {
+        let mut out = String::new();
+        diagnostic(10, 1, 10, 2).render(&mut out, "").unwrap();
+        assert_eq!(out, "error: unsupported type\n  --> src/lib.rs:10:1\n");
+    }
+
+    #[test]
+    fn to_json() {
+        let json = serde_json::from_str::<serde_json::Value>(&diagnostic(2, 17, 2, 21).to_json());
+        assert_eq!(
+            json.unwrap(),
+            serde_json::json!({
+                "message": "unsupported type",
+                "file": "src/lib.rs",
+                "line": 2,
+                "column": 17,
+                "end_line": 2,
+                "end_column": 21,
+            })
+        );
+    }
+}
@@ -1,103 +1,1120 @@
-use sauro_core::syntax;
+use std::collections::{HashMap, HashSet};
 
+use sauro_core::{abi, syntax};
+
+use crate::{
+    config::Integers,
+    jsdoc::JsDoc,
+    platform::{Library, Loader},
+};
+
+/// The name of the file loading the library, shared by the bindings of all the modules
+pub const LIBRARY_FILENAME: &str = "_library.ts";
+
+/// The bindings of a module
+pub struct Bindings {
+    pub source: String,
+    /// The names exported by the bindings
+    pub exports: Vec<String>,
+    /// The path of the module, e.g. `my_crate::foo::api`
+    module_path: String,
+    abi_hash: u64,
+    symbols: Vec<Symbol>,
+}
+
+/// Expand the bindings of a module, the prefix is the path of the library file relative to the
+/// bindings.
 pub fn expand_module(
     module: &syntax::Module,
-    dylib_name: &str,
-    dylib_prefix: &str,
-) -> anyhow::Result<String> {
+    module_path: &str,
+    library_prefix: &str,
+    integers: Integers,
+) -> anyhow::Result<Bindings> {
     use std::fmt::Write;
 
     let mut source = String::new();
+    writeln!(
+        &mut source,
+        r#"import {{ SauroDecodeError, SauroError, SauroPanic, symbols as __symbols }} from "{}{}";"#,
+        library_prefix, LIBRARY_FILENAME
+    )?;
+    writeln!(&mut source)?;
+    writeln!(
+        &mut source,
+        "export {{ SauroDecodeError, SauroError, SauroPanic }};"
+    )?;
+    writeln!(&mut source)?;
 
     let mut structs = String::new();
+    let mut classes = String::new();
     let mut functions = String::new();
+    let mut symbols = vec![];
     let mut utilities = Utilities::default();
 
+    let ctx = Context::new(module, module_path, integers);
+
     for item in &module.items {
         let item_utilities = match item {
-            syntax::Item::Fn(func) => expand_function(&mut functions, func)?,
+            syntax::Item::Enum(enm) if ctx.errors.contains(&enm.ident) => {
+                expand_error_enum(&mut classes, enm)?
+            }
+            syntax::Item::Enum(enm) => expand_enum(&mut structs, enm)?,
+            syntax::Item::Fn(func) => {
+                symbols.push(Symbol::function(
+                    ctx.symbol(&func.sig.ident),
+                    &func.sig,
+                    is_non_blocking_fn(&func.attrs, &func.sig),
+                ));
+                expand_function(&mut functions, func, &ctx)?
+            }
+            syntax::Item::Impl(imp) => {
+                for method in &imp.items {
+                    symbols.push(Symbol::function(
+                        ctx.symbol(&method.symbol(&imp.self_ty)),
+                        &method.sig,
+                        is_non_blocking_fn(&method.attrs, &method.sig),
+                    ));
+                }
+                Utilities::default()
+            }
+            syntax::Item::Opaque(opaque) => {
+                symbols.push(Symbol::release(ctx.symbol(&opaque.free_symbol())));
+                let methods = module
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        syntax::Item::Impl(imp) if imp.self_ty == opaque.item.ident => Some(imp),
+                        _ => None,
+                    })
+                    .flat_map(|imp| &imp.items);
+                expand_class(&mut classes, opaque, methods, &ctx)?
+            }
+            syntax::Item::Struct(strct) if ctx.errors.contains(&strct.ident) => {
+                expand_error_struct(&mut classes, strct)?
+            }
             syntax::Item::Struct(strct) => expand_struct(&mut structs, strct)?,
         };
         utilities.merge(item_utilities);
     }
 
     write!(&mut source, "{}", structs)?;
+    write!(&mut source, "{}", classes)?;
     write!(&mut source, "{}", functions)?;
     utilities.expand(&mut source)?;
 
-    // import external library
-    let functions = module.items.iter().filter_map(|item| match item {
-        syntax::Item::Fn(func) => Some(func),
-        _ => None,
-    });
-    expand_symbols(&mut source, functions, dylib_name, dylib_prefix)?;
+    if utilities.len_prefixed_buffer | utilities.check_error {
+        symbols.push(Symbol::release("sauro_dealloc".to_owned()));
+    }
+
+    let exports = module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syntax::Item::Enum(enm) => Some(enm.ident.to_string()),
+            syntax::Item::Fn(func) => Some(func.sig.ident.to_string()),
+            syntax::Item::Impl(_) => None,
+            syntax::Item::Opaque(opaque) => Some(opaque.item.ident.to_string()),
+            syntax::Item::Struct(strct) => Some(strct.ident.to_string()),
+        })
+        .collect();
+
+    Ok(Bindings {
+        source,
+        exports,
+        module_path: module_path.to_owned(),
+        abi_hash: abi::module_hash(module),
+        symbols,
+    })
+}
+
+/// Expand the file loading the library, the symbols of all the modules are loaded once.
+pub fn expand_library(
+    modules: &[&Bindings],
+    libraries: &[Library],
+    loader: &Loader,
+) -> anyhow::Result<String> {
+    use std::fmt::Write;
+
+    // the symbols shared by the modules (e.g. `sauro_dealloc`) must have the same definition
+    let mut symbols = HashMap::new();
+    for module in modules {
+        for symbol in &module.symbols {
+            match symbols.insert(&symbol.name, (symbol, &module.module_path)) {
+                Some((other, other_path)) if other != symbol => anyhow::bail!(
+                    "symbol {} is defined differently by modules {} and {}",
+                    symbol.name,
+                    other_path,
+                    module.module_path
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    let mut source = String::new();
+    writeln!(&mut source, "{}", ERRORS)?;
+    expand_symbols(&mut source, modules, libraries, loader)?;
+    Ok(source)
+}
+
+/// The file loading the library without the checksums of the libraries, they change whenever a
+/// library is compiled again even if its bindings do not.
+pub fn without_checksums(source: &[u8]) -> Vec<u8> {
+    const PREFIX: &[u8] = b"sha256: '";
+
+    let mut stripped = Vec::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest
+        .windows(PREFIX.len())
+        .position(|window| window == PREFIX)
+    {
+        let (head, tail) = rest.split_at(start + PREFIX.len());
+        stripped.extend_from_slice(head);
+        let end = tail
+            .iter()
+            .position(|byte| *byte == b'\'')
+            .unwrap_or(tail.len());
+        rest = &tail[end..];
+    }
+    stripped.extend_from_slice(rest);
+    stripped
+}
+
+/// Expand the entry point of the package, re-exporting the bindings of all the modules.
+///
+/// The names exported by more modules are ambiguous, they are available only from the namespace
+/// of each module, named after the module path (e.g. `geometry_units` for `my_crate::geometry::units`).
+pub fn expand_entry_point(modules: &[(&str, &Bindings)]) -> anyhow::Result<String> {
+    use std::fmt::Write;
+
+    let mut exported = HashMap::<_, usize>::new();
+    for (_, bindings) in modules {
+        for name in &bindings.exports {
+            *exported.entry(name.as_str()).or_default() += 1;
+        }
+    }
+
+    let mut source = String::new();
+    writeln!(
+        &mut source,
+        r#"export {{ SauroDecodeError, SauroError, SauroPanic }} from "./{}";"#,
+        LIBRARY_FILENAME
+    )?;
+    let mut namespaces = HashSet::new();
+    for (filename, bindings) in modules {
+        let (unique, ambiguous): (Vec<_>, Vec<_>) = bindings
+            .exports
+            .iter()
+            .partition(|name| exported[name.as_str()] == 1);
+        if ambiguous.is_empty() {
+            writeln!(&mut source, r#"export * from "./{}";"#, filename)?;
+            continue;
+        }
 
+        if !unique.is_empty() {
+            let unique = unique.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+            writeln!(
+                &mut source,
+                r#"export {{ {} }} from "./{}";"#,
+                unique.join(", "),
+                filename
+            )?;
+        }
+        let namespace = bindings
+            .module_path
+            .split("::")
+            .skip(1)
+            .collect::<Vec<_>>()
+            .join("_");
+        if exported.contains_key(namespace.as_str()) || !namespaces.insert(namespace.clone()) {
+            anyhow::bail!(
+                "the namespace {} of the bindings of module {} is already exported",
+                namespace,
+                bindings.module_path
+            );
+        }
+        writeln!(
+            &mut source,
+            r#"export * as {} from "./{}";"#,
+            namespace, filename
+        )?;
+    }
     Ok(source)
 }
 
+/// Information about the module shared by all the items
+struct Context<'a> {
+    /// Types of the module used as error of a `Result`, they are exported as classes
+    errors: HashSet<&'a syn::Ident>,
+    /// The path of the module, it is part of the exported symbols
+    module_path: &'a str,
+    /// Mapping of the 64-bit integers of the signatures
+    integers: Integers,
+}
+
+impl<'a> Context<'a> {
+    fn new(module: &'a syntax::Module, module_path: &'a str, integers: Integers) -> Self {
+        let types = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syntax::Item::Enum(enm) => Some(&enm.ident),
+                syntax::Item::Struct(strct) => Some(&strct.ident),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let signatures = module.items.iter().flat_map(|item| match item {
+            syntax::Item::Fn(func) => vec![&func.sig],
+            syntax::Item::Impl(imp) => imp.items.iter().map(|method| &method.sig).collect(),
+            _ => vec![],
+        });
+        let errors = signatures
+            .filter_map(|sig| match &sig.output {
+                syntax::ReturnType::Type(_, ty) => ty.error.as_ref()?.ident(),
+                syntax::ReturnType::Default => None,
+            })
+            .filter(|ident| types.contains(ident))
+            .collect();
+
+        Self {
+            errors,
+            module_path,
+            integers,
+        }
+    }
+
+    /// The symbol exported by the library for the given binding
+    fn symbol(&self, ident: &syn::Ident) -> String {
+        syntax::export_name(self.module_path, ident)
+    }
+
+    /// The mapping of the type if it is a 64-bit integer and the policy is not the default one
+    fn integer_mapping(&self, ty: &syntax::Type) -> Option<Integers> {
+        match ty.kind {
+            syntax::TypeKind::Native(native) | syntax::TypeKind::NonZero(native)
+                if is_64bit(native) && self.integers != Integers::NumberOrBigint =>
+            {
+                Some(self.integers)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_64bit(ty: syntax::TypeNative) -> bool {
+    matches!(
+        ty,
+        syntax::TypeNative::I64
+            | syntax::TypeNative::ISize
+            | syntax::TypeNative::U64
+            | syntax::TypeNative::USize
+    )
+}
+
 fn expand_struct(
     out: &mut impl std::fmt::Write,
     strct: &syntax::ItemStruct,
 ) -> Result<Utilities, std::fmt::Error> {
-    writeln!(out, "export type {} = {{", strct.ident)?;
-    for field in &strct.fields {
-        write!(out, "  {}: ", field.ident)?;
-        expand_type(out, &field.ty)?;
+    JsDoc::new(&strct.attrs).write(out, "")?;
+    match &strct.fields {
+        syntax::Fields::Named(fields) => {
+            let (properties, flattened) = properties(fields, &strct.serde);
+            writeln!(out, "export type {} = {{", strct.ident)?;
+            for property in properties {
+                JsDoc::new(property.attrs).write(out, "  ")?;
+                write!(out, "  {}: ", property)?;
+                expand_type(out, property.ty)?;
+                writeln!(out, ";")?;
+            }
+            write!(out, "}}")?;
+            expand_flattened(out, flattened)?;
+            writeln!(out)?;
+        }
+        // newtypes are branded, they are not interchangeable with the inner type
+        syntax::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            let ty = &fields.unnamed[0].ty.ts;
+            write!(out, "export type {} = ", strct.ident)?;
+            if ty.is_union() {
+                write!(out, "({})", ty)?;
+            } else {
+                write!(out, "{}", ty)?;
+            }
+            writeln!(out, r#" & {{ __brand: "{}" }};"#, strct.ident)?;
+        }
+        fields => {
+            write!(out, "export type {} = ", strct.ident)?;
+            expand_fields(out, fields, &strct.serde)?;
+            writeln!(out, ";")?;
+        }
+    }
+    writeln!(out)?;
+
+    if let Some(fields) = strct.native_fields() {
+        expand_native_struct(out, strct, &fields)?;
+    }
+
+    Ok(Utilities::default())
+}
+
+/// Functions packing and unpacking a struct passed by value, the layout follows `#[repr(C)]`
+fn expand_native_struct(
+    out: &mut impl std::fmt::Write,
+    strct: &syntax::ItemStruct,
+    fields: &[syntax::TypeNative],
+) -> std::fmt::Result {
+    let syntax::Layout { offsets, size, .. } = syntax::Layout::new(fields);
+
+    let accessors = match &strct.fields {
+        syntax::Fields::Named(named) => named
+            .named
+            .iter()
+            .map(|field| format!("v{}", accessor(&serde_name(field, &strct.serde))))
+            .collect(),
+        syntax::Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => vec!["v".to_owned()],
+        syntax::Fields::Unnamed(unnamed) => (0..unnamed.unnamed.len())
+            .map(|index| format!("v[{}]", index))
+            .collect::<Vec<_>>(),
+        syntax::Fields::Unit => vec![],
+    };
+
+    // the data is encoded using little endian, as on the platforms supported by Deno
+    writeln!(
+        out,
+        "function __{0}Encode(v: {0}): Uint8Array {{",
+        strct.ident
+    )?;
+    writeln!(out, "  const buf = new Uint8Array({});", size)?;
+    writeln!(out, "  const view = new DataView(buf.buffer);")?;
+    for ((ty, offset), accessor) in fields.iter().zip(&offsets).zip(&accessors) {
+        let (method, is_bigint) = data_view_method(*ty);
+        let value = match ty {
+            syntax::TypeNative::Bool => format!("{} ? 1 : 0", accessor),
+            _ if is_bigint => format!("BigInt({})", accessor),
+            _ => accessor.to_owned(),
+        };
+        writeln!(
+            out,
+            "  view.set{}({}, {}{});",
+            method,
+            offset,
+            value,
+            little_endian(*ty)
+        )?;
+    }
+    writeln!(out, "  return buf;")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "function __{0}Decode(buf: Uint8Array): {0} {{",
+        strct.ident
+    )?;
+    writeln!(
+        out,
+        "  const view = new DataView(buf.buffer, buf.byteOffset, buf.byteLength);"
+    )?;
+    let getters = fields.iter().zip(&offsets).map(|(ty, offset)| {
+        let method = data_view_method(*ty).0;
+        let getter = format!("view.get{}({}{})", method, offset, little_endian(*ty));
+        match ty {
+            syntax::TypeNative::Bool => format!("{} !== 0", getter),
+            _ => getter,
+        }
+    });
+    match &strct.fields {
+        syntax::Fields::Named(named) => {
+            writeln!(out, "  return {{")?;
+            for (field, getter) in named.named.iter().zip(getters) {
+                let name = property_name(&serde_name(field, &strct.serde));
+                writeln!(out, "    {}: {},", name, getter)?;
+            }
+            writeln!(out, "  }};")?;
+        }
+        syntax::Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+            for getter in getters {
+                writeln!(out, "  return {} as {};", getter, strct.ident)?;
+            }
+        }
+        syntax::Fields::Unnamed(_) => {
+            writeln!(out, "  return [")?;
+            for getter in getters {
+                writeln!(out, "    {},", getter)?;
+            }
+            writeln!(out, "  ];")?;
+        }
+        syntax::Fields::Unit => unreachable!("unit structs are not passed by value"),
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// The endianness argument of the `DataView` methods, single bytes have no endianness
+fn little_endian(ty: syntax::TypeNative) -> &'static str {
+    match ty.size() {
+        1 => "",
+        _ => ", true",
+    }
+}
+
+/// The suffix of the `DataView` methods used to access the type, and if the value is a bigint
+fn data_view_method(ty: syntax::TypeNative) -> (&'static str, bool) {
+    match ty {
+        // booleans are stored as a single byte
+        syntax::TypeNative::Bool => ("Uint8", false),
+        syntax::TypeNative::I8 => ("Int8", false),
+        syntax::TypeNative::I16 => ("Int16", false),
+        syntax::TypeNative::I32 => ("Int32", false),
+        syntax::TypeNative::I64 | syntax::TypeNative::ISize => ("BigInt64", true),
+        syntax::TypeNative::U8 => ("Uint8", false),
+        syntax::TypeNative::U16 => ("Uint16", false),
+        syntax::TypeNative::U32 => ("Uint32", false),
+        syntax::TypeNative::U64 | syntax::TypeNative::USize => ("BigUint64", true),
+        syntax::TypeNative::F32 => ("Float32", false),
+        syntax::TypeNative::F64 => ("Float64", false),
+    }
+}
+
+fn expand_enum(
+    out: &mut impl std::fmt::Write,
+    enm: &syntax::ItemEnum,
+) -> Result<Utilities, std::fmt::Error> {
+    // by default unit variants are encoded as strings, the other ones are encoded as objects
+    // with a single property named as the variant (externally tagged representation), `tag` and
+    // `content` store the name of the variant in a property shared by all the variants
+    let variants = enm
+        .variants
+        .iter()
+        .filter(|variant| !variant.serde.skip)
+        .collect::<Vec<_>>();
+    // variants are written on separate lines when they are documented
+    let documented = variants
+        .iter()
+        .any(|variant| !JsDoc::new(&variant.attrs).is_empty());
+    JsDoc::new(&enm.attrs).write(out, "")?;
+    if variants.is_empty() {
+        writeln!(out, "export type {} = never;", enm.ident)?;
+    } else if enm.is_unit_only() && enm.serde.tag.is_none() && !documented {
+        write!(out, "export type {} =", enm.ident)?;
+        for (index, variant) in variants.iter().enumerate() {
+            if index > 0 {
+                write!(out, " |")?;
+            }
+            write!(out, r#" "{}""#, variant.serde_name(enm.serde.rename_all))?;
+        }
         writeln!(out, ";")?;
+    } else {
+        write!(out, "export type {} =", enm.ident)?;
+        for variant in variants {
+            writeln!(out)?;
+            JsDoc::new(&variant.attrs).write(out, "  ")?;
+            write!(out, "  | ")?;
+            expand_variant(out, variant, &enm.serde)?;
+        }
+        writeln!(out, ";")?;
+    }
+    writeln!(out)?;
+
+    Ok(Utilities::default())
+}
+
+fn expand_variant(
+    out: &mut impl std::fmt::Write,
+    variant: &syntax::Variant,
+    serde: &syntax::Serde,
+) -> std::fmt::Result {
+    let name = variant.serde_name(serde.rename_all);
+    let Some(tag) = &serde.tag else {
+        return match &variant.fields {
+            syntax::Fields::Unit => write!(out, r#""{}""#, name),
+            fields => {
+                write!(out, "{{ {}: ", property_name(&name))?;
+                expand_fields(out, fields, &variant.serde)?;
+                write!(out, " }}")
+            }
+        };
+    };
+
+    write!(out, r#"{{ {}: "{}""#, property_name(tag), name)?;
+    match (&serde.content, &variant.fields) {
+        (_, syntax::Fields::Unit) => write!(out, " }}"),
+        (Some(content), fields) => {
+            write!(out, "; {}: ", property_name(content))?;
+            expand_fields(out, fields, &variant.serde)?;
+            write!(out, " }}")
+        }
+        (None, syntax::Fields::Named(fields)) => {
+            let (properties, flattened) = properties(fields, &variant.serde);
+            for property in properties {
+                write!(out, "; {}: ", property)?;
+                expand_type(out, property.ty)?;
+            }
+            write!(out, " }}")?;
+            expand_flattened(out, flattened)
+        }
+        (None, syntax::Fields::Unnamed(_)) => {
+            unreachable!("internally tagged enums with tuple variants are rejected by the parser")
+        }
+    }
+}
+
+fn expand_error_struct(
+    out: &mut impl std::fmt::Write,
+    strct: &syntax::ItemStruct,
+) -> Result<Utilities, std::fmt::Error> {
+    JsDoc::new(&strct.attrs).write(out, "")?;
+    writeln!(out, "export class {} extends Error {{", strct.ident)?;
+    // the fields are exposed only for structs with named fields
+    let named = match &strct.fields {
+        syntax::Fields::Named(fields) => Some(fields),
+        _ => None,
+    };
+    match named {
+        Some(fields) => {
+            let (properties, flattened) = properties(fields, &strct.serde);
+            for property in properties {
+                JsDoc::new(property.attrs).write(out, "  ")?;
+                write!(out, "  readonly {}: ", property)?;
+                expand_type(out, property.ty)?;
+                writeln!(out, ";")?;
+            }
+            // the properties of flattened fields are only available through the value
+            if !flattened.is_empty() {
+                write!(out, "  readonly value: ")?;
+                expand_fields(out, &strct.fields, &strct.serde)?;
+                writeln!(out, ";")?;
+            }
+        }
+        None => {
+            write!(out, "  readonly value: ")?;
+            expand_fields(out, &strct.fields, &strct.serde)?;
+            writeln!(out, ";")?;
+        }
+    }
+    writeln!(out)?;
+    write!(out, "  constructor(value: ")?;
+    expand_fields(out, &strct.fields, &strct.serde)?;
+    writeln!(out, ", message?: string) {{")?;
+    writeln!(out, "    super(message ?? JSON.stringify(value));")?;
+    writeln!(out, r#"    this.name = "{}";"#, strct.ident)?;
+    match named {
+        Some(fields) => {
+            let (properties, flattened) = properties(fields, &strct.serde);
+            for property in properties {
+                let accessor = accessor(&property.name);
+                writeln!(out, "    this{0} = value{0};", accessor)?;
+            }
+            if !flattened.is_empty() {
+                writeln!(out, "    this.value = value;")?;
+            }
+        }
+        None => writeln!(out, "    this.value = value;")?,
+    }
+    writeln!(out, "  }}")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    Ok(Utilities::default())
+}
+
+fn expand_error_enum(
+    out: &mut impl std::fmt::Write,
+    enm: &syntax::ItemEnum,
+) -> Result<Utilities, std::fmt::Error> {
+    use std::fmt::Write;
+
+    let mut kind = String::new();
+    let mut value = String::new();
+    let variants = enm.variants.iter().filter(|variant| !variant.serde.skip);
+    for (index, variant) in variants.enumerate() {
+        if index > 0 {
+            kind.push_str(" | ");
+            value.push_str(" | ");
+        }
+        write!(kind, r#""{}""#, variant.serde_name(enm.serde.rename_all))?;
+        expand_variant(&mut value, variant, &enm.serde)?;
+    }
+    if kind.is_empty() {
+        kind.push_str("never");
+        value.push_str("never");
     }
+
+    JsDoc::new(&enm.attrs).write(out, "")?;
+    writeln!(out, "export class {} extends Error {{", enm.ident)?;
+    writeln!(out, "  readonly kind: {};", kind)?;
+    writeln!(out, "  readonly value: {};", value)?;
+    writeln!(out)?;
+    writeln!(out, "  constructor(value: {}, message?: string) {{", value)?;
+    writeln!(out, "    super(message ?? JSON.stringify(value));")?;
+    writeln!(out, r#"    this.name = "{}";"#, enm.ident)?;
+    writeln!(out, "    this.value = value;")?;
+    match &enm.serde.tag {
+        Some(tag) => writeln!(out, "    this.kind = value{};", accessor(tag))?,
+        None => writeln!(
+            out,
+            r#"    this.kind = (typeof value === "string" ? value : Object.keys(value)[0]) as {}["kind"];"#,
+            enm.ident
+        )?,
+    }
+    writeln!(out, "  }}")?;
     writeln!(out, "}}")?;
     writeln!(out)?;
 
     Ok(Utilities::default())
 }
 
-fn expand_symbols<'a>(
+fn expand_fields(
     out: &mut impl std::fmt::Write,
-    funcs: impl Iterator<Item = &'a syntax::ItemFn>,
-    dylib: &str,
+    fields: &syntax::Fields,
+    serde: &syntax::Serde,
+) -> std::fmt::Result {
+    match fields {
+        syntax::Fields::Named(fields) => {
+            let (properties, flattened) = properties(fields, serde);
+            write!(out, "{{")?;
+            for (index, property) in properties.into_iter().enumerate() {
+                if index > 0 {
+                    write!(out, ";")?;
+                }
+                write!(out, " {}: ", property)?;
+                expand_type(out, property.ty)?;
+            }
+            write!(out, " }}")?;
+            expand_flattened(out, flattened)?;
+        }
+        // newtype variants are encoded as the inner value
+        syntax::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            expand_type(out, &fields.unnamed[0].ty)?;
+        }
+        syntax::Fields::Unnamed(fields) => {
+            write!(out, "[")?;
+            let fields = fields.unnamed.iter().filter(|field| !field.serde.skip);
+            for (index, field) in fields.enumerate() {
+                if index > 0 {
+                    write!(out, ", ")?;
+                }
+                expand_type(out, &field.ty)?;
+            }
+            write!(out, "]")?;
+        }
+        syntax::Fields::Unit => write!(out, "null")?,
+    }
+    Ok(())
+}
+
+/// A property of the object encoding a struct or a struct variant
+struct Property<'a> {
+    name: String,
+    optional: bool,
+    ty: &'a syntax::Type,
+    attrs: &'a [syn::Attribute],
+}
+
+impl std::fmt::Display for Property<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let optional = if self.optional { "?" } else { "" };
+        write!(f, "{}{}", property_name(&self.name), optional)
+    }
+}
+
+/// The properties of the object encoding the named fields, following the `serde` attributes.
+/// Skipped fields are removed and the types of the flattened fields are returned apart.
+fn properties<'a>(
+    fields: &'a syntax::FieldsNamed,
+    serde: &syntax::Serde,
+) -> (Vec<Property<'a>>, Vec<&'a syntax::Type>) {
+    let mut properties = vec![];
+    let mut flattened = vec![];
+    for field in fields.named.iter().filter(|field| !field.serde.skip) {
+        if field.serde.flatten {
+            flattened.push(&field.ty);
+        } else {
+            properties.push(Property {
+                name: serde_name(field, serde),
+                optional: serde.optional || field.serde.optional,
+                ty: &field.ty,
+                attrs: &field.attrs,
+            });
+        }
+    }
+    (properties, flattened)
+}
+
+/// The flattened fields are merged in the object, the result is the intersection of the types
+fn expand_flattened(out: &mut impl std::fmt::Write, types: Vec<&syntax::Type>) -> std::fmt::Result {
+    for ty in types {
+        if ty.ts.is_union() {
+            write!(out, " & ({})", ty.ts)?;
+        } else {
+            write!(out, " & {}", ty.ts)?;
+        }
+    }
+    Ok(())
+}
+
+/// The name of a named field in the encoded object
+fn serde_name(field: &syntax::Field, serde: &syntax::Serde) -> String {
+    field
+        .serde_name(serde.rename_all)
+        .expect("expected a named field")
+}
+
+/// The property name as written in an object type, quoted if not a valid identifier
+fn property_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_owned()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+/// The expression accessing the property of an object
+fn accessor(name: &str) -> String {
+    if is_identifier(name) {
+        format!(".{}", name)
+    } else {
+        format!("[{:?}]", name)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_' || ch == '$')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '$')
+}
+
+/// A symbol imported from the dynamic library, types are rendered as in the symbol definition
+#[derive(PartialEq, Eq)]
+struct Symbol {
+    name: String,
+    parameters: Vec<String>,
+    result: String,
+    non_blocking: bool,
+}
+
+impl Symbol {
+    fn function(name: String, sig: &syntax::Signature, non_blocking: bool) -> Self {
+        let receiver = sig.receiver.as_ref().map(|_| r#""pointer""#.to_owned());
+        // the last parameter is used to report errors
+        let parameters = receiver
+            .into_iter()
+            .chain(sig.inputs.iter().map(|input| symbol_type(&input.ty)))
+            .chain([r#""buffer""#.to_owned()])
+            .collect();
+        let result = match &sig.output {
+            syntax::ReturnType::Default => r#""void""#.to_owned(),
+            syntax::ReturnType::Type(_, ty) => symbol_return_type(ty),
+        };
+
+        Self {
+            name,
+            parameters,
+            result,
+            non_blocking,
+        }
+    }
+
+    /// Symbol that takes ownership of a pointer and releases it
+    fn release(name: String) -> Self {
+        Self {
+            name,
+            parameters: vec![r#""pointer""#.to_owned()],
+            result: r#""void""#.to_owned(),
+            non_blocking: false,
+        }
+    }
+}
+
+/// The library is loaded from the directory of its platform, next to the bindings. When it is
+/// missing, the library is searched in the search paths.
+fn expand_local_loader(
+    out: &mut impl std::fmt::Write,
+    libraries: &[Library],
     prefix: &str,
+    search_paths: &[String],
+) -> std::fmt::Result {
+    writeln!(out, r#"const __dylib = (() => {{"#)?;
+    writeln!(
+        out,
+        r#"  const platform = `${{Deno.build.os}}-${{Deno.build.arch}}`;"#
+    )?;
+    writeln!(out, r#"  const filename: string | undefined = ({{"#)?;
+    for library in libraries {
+        writeln!(
+            out,
+            r#"    "{0}": '{1}{0}/{2}',"#,
+            library.platform, prefix, library.filename
+        )?;
+    }
+    writeln!(out, r#"  }} as Record<string, string>)[platform];"#)?;
+    writeln!(out, r#"  if (filename === undefined) {{"#)?;
+    writeln!(
+        out,
+        r#"    throw new Error(`the library is not available for ${{platform}}`);"#
+    )?;
+    writeln!(out, r#"  }}"#)?;
+    if search_paths.is_empty() {
+        writeln!(out, r#"  return new URL(filename, import.meta.url);"#)?;
+    } else {
+        writeln!(out, r#"  const basename = filename.split("/").pop();"#)?;
+        writeln!(out, r#"  const candidates = ["#)?;
+        writeln!(out, r#"    new URL(filename, import.meta.url),"#)?;
+        for path in search_paths {
+            writeln!(
+                out,
+                r#"    `{}/${{basename}}`,"#,
+                path.trim_end_matches(['/', '\\'])
+            )?;
+        }
+        writeln!(out, r#"  ];"#)?;
+        writeln!(out, r#"  for (const candidate of candidates) {{"#)?;
+        writeln!(out, r#"    try {{"#)?;
+        writeln!(out, r#"      Deno.statSync(candidate);"#)?;
+        writeln!(out, r#"      return candidate;"#)?;
+        writeln!(out, r#"    }} catch (error) {{"#)?;
+        writeln!(
+            out,
+            r#"      if (!(error instanceof Deno.errors.NotFound)) throw error;"#
+        )?;
+        writeln!(out, r#"    }}"#)?;
+        writeln!(out, r#"  }}"#)?;
+        writeln!(
+            out,
+            r#"  throw new Error(`the library is missing (${{candidates.join(", ")}})`);"#
+        )?;
+    }
+    writeln!(out, r#"}})();"#)?;
+    writeln!(out)
+}
+
+/// The library is downloaded from the base URL into the cache, Deno can not open a remote
+/// library. The checksum of the library is verified before it is opened.
+///
+/// The cache is stored in the Deno directory, it can be changed using `SAURO_CACHE_DIR`.
+fn expand_remote_loader(
+    out: &mut impl std::fmt::Write,
+    libraries: &[Library],
+    base_url: &str,
 ) -> std::fmt::Result {
-    writeln!(out, r#"const {{ symbols: __symbols }} = Deno.dlopen("#)?;
-    writeln!(out, r#"  new URL("#)?;
-    writeln!(out, r#"    {{"#)?;
-    writeln!(out, r#"      darwin: '{}lib{}.dylib',"#, prefix, dylib)?;
-    writeln!(out, r#"      linux: '{}lib{}.so',"#, prefix, dylib)?;
-    writeln!(out, r#"      windows: '{}{}.dll',"#, prefix, dylib)?;
-    writeln!(out, r#"      freebsd: '{}lib{}.so',"#, prefix, dylib)?;
-    writeln!(out, r#"      netbsd: '{}lib{}.so',"#, prefix, dylib)?;
-    writeln!(out, r#"      aix: '{}lib{}.so',"#, prefix, dylib)?;
-    writeln!(out, r#"      solaris: '{}lib{}.so',"#, prefix, dylib)?;
-    writeln!(out, r#"      illumos: '{}lib{}.so',"#, prefix, dylib)?;
-    writeln!(out, r#"    }}[Deno.build.os],"#)?;
-    writeln!(out, r#"    import.meta.url"#)?;
-    writeln!(out, r#"  ),"#)?;
+    let base_url = if base_url.ends_with('/') {
+        base_url.to_owned()
+    } else {
+        format!("{}/", base_url)
+    };
+
+    writeln!(out, r#"const __dylib = await (async () => {{"#)?;
+    // the URL is not written in a template literal, it could contain `${`
+    writeln!(
+        out,
+        r#"  const baseUrl = {};"#,
+        serde_json::to_string(&base_url).unwrap()
+    )?;
+    writeln!(
+        out,
+        r#"  const platform = `${{Deno.build.os}}-${{Deno.build.arch}}`;"#
+    )?;
+    writeln!(
+        out,
+        r#"  const library: {{ filename: string; sha256: string }} | undefined = ({{"#
+    )?;
+    for library in libraries {
+        writeln!(
+            out,
+            r#"    "{}": {{ filename: '{}', sha256: '{}' }},"#,
+            library.platform, library.filename, library.sha256
+        )?;
+    }
+    writeln!(
+        out,
+        r#"  }} as Record<string, {{ filename: string; sha256: string }}>)[platform];"#
+    )?;
+    writeln!(out, r#"  if (library === undefined) {{"#)?;
+    writeln!(
+        out,
+        r#"    throw new Error(`the library is not available for ${{platform}}`);"#
+    )?;
+    writeln!(out, r#"  }}"#)?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        r#"  const sha256 = async (data: Uint8Array): Promise<string> =>"#
+    )?;
+    writeln!(
+        out,
+        r#"    Array.from(new Uint8Array(await crypto.subtle.digest("SHA-256", data)))"#
+    )?;
+    writeln!(
+        out,
+        r#"      .map((byte) => byte.toString(16).padStart(2, "0"))"#
+    )?;
+    writeln!(out, r#"      .join("");"#)?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        r#"  const env = (name: string) => Deno.env.get(name);"#
+    )?;
+    writeln!(out, r#"  const denoDir = env("DENO_DIR") ?? ("#)?;
+    writeln!(out, r#"    Deno.build.os === "windows""#)?;
+    writeln!(out, r#"      ? `${{env("LOCALAPPDATA")}}/deno`"#)?;
+    writeln!(out, r#"      : Deno.build.os === "darwin""#)?;
+    writeln!(out, r#"      ? `${{env("HOME")}}/Library/Caches/deno`"#)?;
+    writeln!(
+        out,
+        r#"      : `${{env("XDG_CACHE_HOME") ?? `${{env("HOME")}}/.cache`}}/deno`"#
+    )?;
+    writeln!(out, r#"  );"#)?;
+    writeln!(
+        out,
+        r#"  const cache = env("SAURO_CACHE_DIR") ?? `${{denoDir}}/sauro`;"#
+    )?;
+    writeln!(out, r#"  const dir = `${{cache}}/${{library.sha256}}`;"#)?;
+    writeln!(out, r#"  const path = `${{dir}}/${{library.filename}}`;"#)?;
+    writeln!(out)?;
+    writeln!(out, r#"  let data: Uint8Array | undefined;"#)?;
+    writeln!(out, r#"  try {{"#)?;
+    writeln!(out, r#"    data = await Deno.readFile(path);"#)?;
+    writeln!(out, r#"  }} catch (error) {{"#)?;
+    writeln!(
+        out,
+        r#"    if (!(error instanceof Deno.errors.NotFound)) throw error;"#
+    )?;
+    writeln!(out, r#"  }}"#)?;
+    writeln!(
+        out,
+        r#"  if (data === undefined || await sha256(data) !== library.sha256) {{"#
+    )?;
+    writeln!(
+        out,
+        r#"    const url = new URL(baseUrl + `${{platform}}/${{library.filename}}`, import.meta.url);"#
+    )?;
+    writeln!(out, r#"    const response = await fetch(url);"#)?;
+    writeln!(out, r#"    if (!response.ok) {{"#)?;
+    writeln!(
+        out,
+        r#"      throw new Error(`failed to download the library ${{url}}: ${{response.status}} ${{response.statusText}}`);"#
+    )?;
+    writeln!(out, r#"    }}"#)?;
+    writeln!(
+        out,
+        r#"    data = new Uint8Array(await response.arrayBuffer());"#
+    )?;
+    writeln!(out, r#"    const checksum = await sha256(data);"#)?;
+    writeln!(out, r#"    if (checksum !== library.sha256) {{"#)?;
+    writeln!(
+        out,
+        r#"      throw new Error(`checksum mismatch of the library ${{url}} (expected ${{library.sha256}}, found ${{checksum}})`);"#
+    )?;
+    writeln!(out, r#"    }}"#)?;
+    writeln!(out, r#"    await Deno.mkdir(dir, {{ recursive: true }});"#)?;
+    // the library is renamed once written, other loaders never read a partial file
+    writeln!(
+        out,
+        r#"    const temp = await Deno.makeTempFile({{ dir, prefix: ".download-" }});"#
+    )?;
+    writeln!(out, r#"    try {{"#)?;
+    writeln!(out, r#"      await Deno.writeFile(temp, data);"#)?;
+    writeln!(out, r#"      await Deno.rename(temp, path);"#)?;
+    writeln!(out, r#"    }} catch (error) {{"#)?;
+    writeln!(out, r#"      await Deno.remove(temp).catch(() => {{}});"#)?;
+    writeln!(out, r#"      throw error;"#)?;
+    writeln!(out, r#"    }}"#)?;
+    writeln!(out, r#"  }}"#)?;
+    writeln!(out, r#"  return path;"#)?;
+    writeln!(out, r#"}})();"#)?;
+    writeln!(out)
+}
+
+fn expand_symbols(
+    out: &mut impl std::fmt::Write,
+    modules: &[&Bindings],
+    libraries: &[Library],
+    loader: &Loader,
+) -> std::fmt::Result {
+    match loader {
+        Loader::Local {
+            prefix,
+            search_paths,
+        } => expand_local_loader(out, libraries, prefix, search_paths)?,
+        Loader::Remote { base_url } => expand_remote_loader(out, libraries, base_url)?,
+    }
+
+    // check the fingerprints before loading the symbols, they could be missing, the library is
+    // opened once for all the modules
+    if !modules.is_empty() {
+        writeln!(out, r#"{{"#)?;
+        writeln!(out, r#"  const __abi = Deno.dlopen(__dylib, {{"#)?;
+        for module in modules {
+            writeln!(
+                out,
+                r#"    "{}": {{ "parameters": [], "result": "u64" }},"#,
+                abi::symbol(&module.module_path)
+            )?;
+        }
+        writeln!(out, r#"  }});"#)?;
+        writeln!(
+            out,
+            r#"  const __check = (module: string, hash: bigint, expected: bigint) => {{"#
+        )?;
+        writeln!(out, r#"    if (hash !== expected) {{"#)?;
+        writeln!(
+            out,
+            r#"      const hex = (v: bigint) => v.toString(16).padStart(16, "0");"#
+        )?;
+        writeln!(out, r#"      throw new Error("#)?;
+        writeln!(
+            out,
+            r#"        `ABI mismatch between the bindings of module ${{module}} and the library ${{__dylib}}, the bindings must be generated again (expected 0x${{hex(expected)}}, found 0x${{hex(hash)}})`,"#
+        )?;
+        writeln!(out, r#"      );"#)?;
+        writeln!(out, r#"    }}"#)?;
+        writeln!(out, r#"  }};"#)?;
+        writeln!(out, r#"  try {{"#)?;
+        for module in modules {
+            writeln!(
+                out,
+                r#"    __check("{}", BigInt(__abi.symbols["{}"]()), 0x{:016x}n);"#,
+                module.module_path,
+                abi::symbol(&module.module_path),
+                module.abi_hash
+            )?;
+        }
+        writeln!(out, r#"  }} finally {{"#)?;
+        writeln!(out, r#"    __abi.close();"#)?;
+        writeln!(out, r#"  }}"#)?;
+        writeln!(out, r#"}}"#)?;
+        writeln!(out)?;
+    }
+
+    writeln!(out, r#"export const {{ symbols }} = Deno.dlopen("#)?;
+    writeln!(out, r#"  __dylib,"#)?;
     writeln!(out, r#"  {{"#)?;
 
-    for func in funcs {
-        let sig = &func.sig;
-        writeln!(out, r#"    "{}": {{"#, sig.ident)?;
+    // the symbols used by more modules are loaded once, they are checked by `expand_library`
+    let mut loaded = HashSet::new();
+    let symbols = modules.iter().flat_map(|module| &module.symbols);
+    for symbol in symbols.filter(|symbol| loaded.insert(&symbol.name)) {
+        writeln!(out, r#"    "{}": {{"#, symbol.name)?;
 
         // input parameters
         write!(out, r#"      "parameters": ["#)?;
-        for (index, input) in sig.inputs.iter().enumerate() {
+        for (index, parameter) in symbol.parameters.iter().enumerate() {
             if index > 0 {
                 write!(out, ", ")?;
             }
-            write!(out, r#""{}""#, symbol_type(&input.ty))?;
+            write!(out, "{}", parameter)?;
         }
         writeln!(out, "],")?;
 
         // output results
-        match &sig.output {
-            syntax::ReturnType::Default => writeln!(out, r#"      "result": "void","#)?,
-            syntax::ReturnType::Type(_, ty) => {
-                writeln!(out, r#"      "result": "{}","#, symbol_return_type(ty))?
-            }
-        }
+        writeln!(out, r#"      "result": {},"#, symbol.result)?;
 
         // non blocking
-        let non_blocking = is_non_blocking_fn(func);
-        writeln!(out, r#"      "nonblocking": {:?},"#, non_blocking)?;
+        writeln!(out, r#"      "nonblocking": {:?},"#, symbol.non_blocking)?;
 
         writeln!(out, r#"    }},"#)?;
     }
@@ -110,23 +1127,141 @@ fn expand_symbols<'a>(
 fn expand_function(
     out: &mut impl std::fmt::Write,
     func: &syntax::ItemFn,
+    ctx: &Context,
 ) -> Result<Utilities, std::fmt::Error> {
     let sig = &func.sig;
-    let non_blocking = is_non_blocking_fn(func);
-    let mut utilities = Utilities::default();
+    let non_blocking = is_non_blocking_fn(&func.attrs, &func.sig);
 
-    // signature
+    fn_doc(&func.attrs, sig, ctx).write(out, "")?;
     if non_blocking {
-        write!(out, "export async function {}(", sig.ident)?;
+        write!(out, "export async function ")?;
     } else {
-        write!(out, "export function {}(", sig.ident)?;
+        write!(out, "export function ")?;
+    }
+    expand_signature(out, sig, non_blocking, ctx)?;
+    writeln!(out, " {{")?;
+    let symbol = ctx.symbol(&sig.ident);
+    let utilities = expand_function_body(out, sig, &symbol, non_blocking, "  ", ctx)?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    Ok(utilities)
+}
+
+fn expand_class<'a>(
+    out: &mut impl std::fmt::Write,
+    opaque: &syntax::ItemOpaque,
+    methods: impl Iterator<Item = &'a syntax::ImplItemFn>,
+    ctx: &Context,
+) -> Result<Utilities, std::fmt::Error> {
+    let ident = &opaque.item.ident;
+    let free_symbol = ctx.symbol(&opaque.free_symbol());
+    let mut utilities = Utilities::default();
+
+    // release the handles that are garbage collected without calling `free`
+    writeln!(
+        out,
+        "const __{}Finalizer = new FinalizationRegistry<Deno.PointerValue>((ptr) => {{",
+        ident
+    )?;
+    writeln!(out, r#"  __symbols["{}"](ptr);"#, free_symbol)?;
+    writeln!(out, "}});")?;
+    writeln!(out)?;
+
+    JsDoc::new(&opaque.item.attrs).write(out, "")?;
+    writeln!(out, "export class {} {{", ident)?;
+    writeln!(out, "  #ptr: Deno.PointerValue;")?;
+    // the handle is borrowed by a single call at a time, the library could run it on another
+    // thread or call back into the class
+    writeln!(out, "  #busy = false;")?;
+    writeln!(out)?;
+    writeln!(out, "  private constructor(ptr: Deno.PointerValue) {{")?;
+    writeln!(out, "    this.#ptr = ptr;")?;
+    writeln!(out, "    __{}Finalizer.register(this, ptr, this);", ident)?;
+    writeln!(out, "  }}")?;
+    writeln!(out)?;
+
+    for method in methods {
+        let sig = &method.sig;
+        let non_blocking = is_non_blocking_fn(&method.attrs, &method.sig);
+
+        fn_doc(&method.attrs, sig, ctx).write(out, "  ")?;
+        write!(out, "  ")?;
+        if sig.receiver.is_none() {
+            write!(out, "static ")?;
+        }
+        if non_blocking {
+            write!(out, "async ")?;
+        }
+        expand_signature(out, sig, non_blocking, ctx)?;
+        writeln!(out, " {{")?;
+        let symbol = ctx.symbol(&method.symbol(ident));
+        let method_utilities = expand_function_body(out, sig, &symbol, non_blocking, "    ", ctx)?;
+        utilities.merge(method_utilities);
+        writeln!(out, "  }}")?;
+        writeln!(out)?;
     }
+
+    writeln!(out, "  free(): void {{")?;
+    writeln!(out, "    if (this.#busy) {{")?;
+    writeln!(
+        out,
+        r#"      throw new Deno.errors.BadResource("{} is used by a pending call");"#,
+        ident
+    )?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "    if (this.#ptr !== null) {{")?;
+    writeln!(out, "      __{}Finalizer.unregister(this);", ident)?;
+    writeln!(out, r#"      __symbols["{}"](this.#ptr);"#, free_symbol)?;
+    writeln!(out, "      this.#ptr = null;")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "  }}")?;
+    writeln!(out)?;
+    writeln!(out, "  [Symbol.dispose](): void {{")?;
+    writeln!(out, "    this.free();")?;
+    writeln!(out, "  }}")?;
+    writeln!(out)?;
+    writeln!(out, "  #handle(): Deno.PointerValue {{")?;
+    writeln!(out, "    if (this.#ptr === null) {{")?;
+    writeln!(
+        out,
+        r#"      throw new Deno.errors.BadResource("{} has been already freed");"#,
+        ident
+    )?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "    if (this.#busy) {{")?;
+    writeln!(
+        out,
+        r#"      throw new Deno.errors.BadResource("{} is used by a pending call");"#,
+        ident
+    )?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "    this.#busy = true;")?;
+    writeln!(out, "    return this.#ptr;")?;
+    writeln!(out, "  }}")?;
+    writeln!(out)?;
+    writeln!(out, "  #release(): void {{")?;
+    writeln!(out, "    this.#busy = false;")?;
+    writeln!(out, "  }}")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    Ok(utilities)
+}
+
+fn expand_signature(
+    out: &mut impl std::fmt::Write,
+    sig: &syntax::Signature,
+    non_blocking: bool,
+    ctx: &Context,
+) -> std::fmt::Result {
+    write!(out, "{}(", sig.ident)?;
     for (index, input) in sig.inputs.iter().enumerate() {
         if index > 0 {
             write!(out, ", ")?;
         }
         write!(out, "{}: ", input.ident)?;
-        expand_type(out, &input.ty)?;
+        expand_signature_type(out, &input.ty, ctx)?;
     }
     write!(out, ")")?;
     if let syntax::ReturnType::Type(_, ty) = &sig.output {
@@ -134,114 +1269,409 @@ fn expand_function(
         if non_blocking {
             write!(out, "Promise<")?;
         }
-        expand_type(out, ty)?;
+        expand_signature_type(out, ty, ctx)?;
         if non_blocking {
             write!(out, ">")?;
         }
     }
-    writeln!(out, " {{")?;
+    Ok(())
+}
+
+fn expand_function_body(
+    out: &mut impl std::fmt::Write,
+    sig: &syntax::Signature,
+    symbol: &str,
+    non_blocking: bool,
+    indent: &str,
+    ctx: &Context,
+) -> Result<Utilities, std::fmt::Error> {
+    let mut utilities = Utilities::default();
 
     // transform input
     for (index, input) in sig.inputs.iter().enumerate() {
-        match input.ty.kind {
+        match &input.ty.kind {
             syntax::TypeKind::Native(_) => {
-                writeln!(out, "  const __arg{} = {};", index, input.ident)?;
+                writeln!(out, "{}const __arg{} = {};", indent, index, input.ident)?;
+            }
+            syntax::TypeKind::NonZero(ty) => {
+                match ctx.integer_mapping(&input.ty) {
+                    Some(Integers::Bigint) => {
+                        writeln!(out, "{}if ({} === 0n) {{", indent, input.ident)?
+                    }
+                    None if is_64bit(*ty) => writeln!(
+                        out,
+                        "{0}if ({1} === 0 || {1} === 0n) {{",
+                        indent, input.ident
+                    )?,
+                    _ => writeln!(out, "{}if ({} === 0) {{", indent, input.ident)?,
+                }
+                writeln!(
+                    out,
+                    r#"{}  throw new RangeError("'{}' must be non-zero");"#,
+                    indent, input.ident
+                )?;
+                writeln!(out, "{}}}", indent)?;
+                writeln!(out, "{}const __arg{} = {};", indent, index, input.ident)?;
+            }
+            syntax::TypeKind::I128 | syntax::TypeKind::U128 => {
+                let signed = matches!(input.ty.kind, syntax::TypeKind::I128);
+                writeln!(
+                    out,
+                    "{}const __arg{}_ptr = __int128Encode({}, {});",
+                    indent, index, input.ident, signed
+                )?;
+                writeln!(
+                    out,
+                    "{}const __arg{1}_len = __arg{1}_ptr.byteLength;",
+                    indent, index
+                )?;
+                utilities.int128_encode = true;
+            }
+            syntax::TypeKind::ArrayBorrowed(_, len)
+            | syntax::TypeKind::ArrayBorrowedMut(_, len)
+            | syntax::TypeKind::ArrayOwned(_, len) => {
+                writeln!(out, "{}if ({}.length !== {}) {{", indent, input.ident, len)?;
+                writeln!(
+                    out,
+                    r#"{}  throw new RangeError("expected {} elements in '{}'");"#,
+                    indent, len, input.ident
+                )?;
+                writeln!(out, "{}}}", indent)?;
+                writeln!(out, "{}const __arg{}_ptr = {};", indent, index, input.ident)?;
+                writeln!(
+                    out,
+                    "{}const __arg{1}_len = __arg{1}_ptr.byteLength;",
+                    indent, index
+                )?;
             }
             syntax::TypeKind::BufferBorrowed(_)
             | syntax::TypeKind::BufferBorrowedMut(_)
             | syntax::TypeKind::BufferOwned(_) => {
-                writeln!(out, "  const __arg{}_ptr = {};", index, input.ident)?;
+                writeln!(out, "{}const __arg{}_ptr = {};", indent, index, input.ident)?;
                 writeln!(
                     out,
-                    "  const __arg{0}_len = __arg{0}_ptr.byteLength;",
-                    index
+                    "{}const __arg{1}_len = __arg{1}_ptr.byteLength;",
+                    indent, index
                 )?;
             }
             syntax::TypeKind::StringBorrowed | syntax::TypeKind::StringOwned => {
                 writeln!(
                     out,
-                    "  const __arg{}_ptr = __stringEncode({});",
-                    index, input.ident
+                    "{}const __arg{}_ptr = __stringEncode({});",
+                    indent, index, input.ident
                 )?;
                 writeln!(
                     out,
-                    "  const __arg{0}_len = __arg{0}_ptr.byteLength;",
-                    index
+                    "{}const __arg{1}_len = __arg{1}_ptr.byteLength;",
+                    indent, index
+                )?;
+                utilities.string_encode = true;
+            }
+            // `JSON.stringify(undefined)` is not a JSON value
+            syntax::TypeKind::Json if input.ty.is_unit() => {
+                writeln!(
+                    out,
+                    r#"{}const __arg{}_ptr = __stringEncode("null");"#,
+                    indent, index
+                )?;
+                writeln!(
+                    out,
+                    "{}const __arg{1}_len = __arg{1}_ptr.byteLength;",
+                    indent, index
                 )?;
                 utilities.string_encode = true;
             }
             syntax::TypeKind::Json => {
+                if input.ty.is_char() {
+                    // a `char` is a single code point, not a single UTF-16 unit
+                    writeln!(out, "{}if ([...{}].length !== 1) {{", indent, input.ident)?;
+                    writeln!(
+                        out,
+                        r#"{}  throw new RangeError("'{}' must be a single character");"#,
+                        indent, input.ident
+                    )?;
+                    writeln!(out, "{}}}", indent)?;
+                }
                 writeln!(
                     out,
-                    "  const __arg{}_ptr = __structEncode({});",
-                    index, input.ident
+                    "{}const __arg{}_ptr = __structEncode({});",
+                    indent, index, input.ident
                 )?;
                 writeln!(
                     out,
-                    "  const __arg{0}_len = __arg{0}_ptr.byteLength;",
-                    index
+                    "{}const __arg{1}_len = __arg{1}_ptr.byteLength;",
+                    indent, index
                 )?;
                 utilities.struct_encode = true;
             }
+            syntax::TypeKind::Struct(_) => {
+                writeln!(
+                    out,
+                    "{}const __arg{} = __{}Encode({});",
+                    indent, index, input.ty.ts, input.ident
+                )?;
+            }
+            // the callbacks are created once all the arguments are checked
+            syntax::TypeKind::Callback(_) => {}
+            syntax::TypeKind::Opaque => unreachable!("opaque handles are not valid arguments"),
         }
     }
+    // transform receiver, the handle is borrowed until the call returns
+    if sig.receiver.is_some() {
+        writeln!(out, "{}const __self = this.#handle();", indent)?;
+    }
+    let mut closed = vec![];
+    for (index, input) in sig.inputs.iter().enumerate() {
+        if let syntax::TypeKind::Callback(callback) = &input.ty.kind {
+            let callback_utilities = expand_callback(
+                out,
+                callback,
+                &format!("__arg{}", index),
+                &input.ident.to_string(),
+                non_blocking,
+                indent,
+            )?;
+            utilities.merge(callback_utilities);
+            if !matches!(callback.mode, syntax::CallbackMode::Pointer) {
+                closed.push(index);
+            }
+        }
+    }
+
+    // callbacks and the handle are released as soon as the call returns, even if it fails
+    let is_released = !closed.is_empty() || sig.receiver.is_some();
+    let outer_indent = indent;
+    let inner_indent = format!("{}  ", indent);
+    let indent = if !is_released {
+        indent
+    } else {
+        writeln!(out, "{}try {{", outer_indent)?;
+        inner_indent.as_str()
+    };
 
     // call imported function
+    writeln!(out, "{}const __error = new BigUint64Array(1);", indent)?;
     if non_blocking {
-        write!(out, "  const __inner_res = await __symbols.{}(", sig.ident)?;
+        write!(
+            out,
+            r#"{}const __inner_res = await __symbols["{}"]("#,
+            indent, symbol
+        )?;
     } else {
-        write!(out, "  const __inner_res = __symbols.{}(", sig.ident)?;
+        write!(
+            out,
+            r#"{}const __inner_res = __symbols["{}"]("#,
+            indent, symbol
+        )?;
+    }
+    if sig.receiver.is_some() {
+        write!(out, "__self")?;
     }
     for (index, input) in sig.inputs.iter().enumerate() {
-        if index > 0 {
+        if index > 0 || sig.receiver.is_some() {
             write!(out, ", ")?;
         }
         match input.ty.kind {
-            syntax::TypeKind::Native(_) => write!(out, "__arg{}", index)?,
+            syntax::TypeKind::Native(_)
+            | syntax::TypeKind::NonZero(_)
+            | syntax::TypeKind::Struct(_)
+            | syntax::TypeKind::Callback(_) => write!(out, "__arg{}", index)?,
             _ => write!(out, "__arg{0}_ptr, __arg{0}_len", index)?,
         }
     }
-    writeln!(out, ");")?;
+    if !sig.inputs.is_empty() || sig.receiver.is_some() {
+        write!(out, ", ")?;
+    }
+    writeln!(out, "__error);")?;
+    writeln!(out, "{}__checkError(__error);", indent)?;
+    utilities.check_error = true;
 
     // transform result
     if let syntax::ReturnType::Type(_, ty) = &sig.output {
         match ty.kind {
-            syntax::TypeKind::Native(_) => {
-                writeln!(out, "  return __inner_res")?;
+            syntax::TypeKind::Native(_) | syntax::TypeKind::NonZero(_) => {
+                match ctx.integer_mapping(ty) {
+                    Some(Integers::Bigint) => {
+                        writeln!(out, "{}return BigInt(__inner_res);", indent)?
+                    }
+                    Some(Integers::Number) => {
+                        writeln!(out, "{}return Number(__inner_res);", indent)?
+                    }
+                    _ => writeln!(out, "{}return __inner_res;", indent)?,
+                }
             }
-            syntax::TypeKind::BufferBorrowed(_)
+            syntax::TypeKind::I128 | syntax::TypeKind::U128 => {
+                let signed = matches!(ty.kind, syntax::TypeKind::I128);
+                writeln!(
+                    out,
+                    "{}return __int128Decode(__lenPrefixedBuffer(__inner_res), {});",
+                    indent, signed
+                )?;
+                utilities.int128_decode = true;
+                utilities.len_prefixed_buffer = true;
+            }
+            syntax::TypeKind::ArrayBorrowed(..)
+            | syntax::TypeKind::ArrayBorrowedMut(..)
+            | syntax::TypeKind::ArrayOwned(..)
+            | syntax::TypeKind::BufferBorrowed(_)
             | syntax::TypeKind::BufferBorrowedMut(_)
             | syntax::TypeKind::BufferOwned(_) => {
                 writeln!(
                     out,
-                    "  return new {}(__lenPrefixedBuffer(__inner_res));",
-                    ty.ts
+                    "{}return new {}(__lenPrefixedBuffer(__inner_res));",
+                    indent, ty.ts
                 )?;
                 utilities.len_prefixed_buffer = true;
             }
             syntax::TypeKind::StringBorrowed | syntax::TypeKind::StringOwned => {
                 writeln!(
                     out,
-                    "  return __stringDecode(__lenPrefixedBuffer(__inner_res));"
+                    "{}return __stringDecode(__lenPrefixedBuffer(__inner_res));",
+                    indent
                 )?;
                 utilities.string_decode = true;
                 utilities.len_prefixed_buffer = true;
             }
+            syntax::TypeKind::Json if ty.is_result() => {
+                writeln!(
+                    out,
+                    "{}return __resultDecode(__lenPrefixedBuffer(__inner_res), {});",
+                    indent,
+                    error_class(ty, ctx)
+                )?;
+                utilities.result_decode = true;
+                utilities.len_prefixed_buffer = true;
+            }
             syntax::TypeKind::Json => {
-                let is_result = ty.is_result;
                 writeln!(
                     out,
-                    "  return __structDecode(__lenPrefixedBuffer(__inner_res), {});",
-                    is_result
+                    "{}return __structDecode(__lenPrefixedBuffer(__inner_res));",
+                    indent
                 )?;
                 utilities.struct_decode = true;
                 utilities.len_prefixed_buffer = true;
             }
+            syntax::TypeKind::Opaque => {
+                writeln!(out, "{}return new {}(__inner_res);", indent, ty.ts)?;
+            }
+            syntax::TypeKind::Struct(_) => {
+                writeln!(out, "{}return __{}Decode(__inner_res);", indent, ty.ts)?;
+            }
+            syntax::TypeKind::Callback(_) => unreachable!("callbacks are not valid results"),
         }
     }
 
-    writeln!(out, "}}")?;
-    writeln!(out)?;
+    if is_released {
+        writeln!(out, "{}}} finally {{", outer_indent)?;
+        for index in closed {
+            writeln!(out, "{}__arg{}.close();", indent, index)?;
+        }
+        if sig.receiver.is_some() {
+            writeln!(out, "{}this.#release();", indent)?;
+        }
+        writeln!(out, "{}}}", outer_indent)?;
+    }
+
+    Ok(utilities)
+}
+
+/// The class of the errors thrown when the result is an error, the class generated for the error
+/// type if any
+fn error_class(ty: &syntax::Type, ctx: &Context) -> String {
+    let error = ty
+        .error
+        .as_ref()
+        .and_then(|err| err.ident())
+        .filter(|ident| ctx.errors.contains(ident));
+    match error {
+        Some(ident) => ident.to_string(),
+        None => "SauroError".to_owned(),
+    }
+}
+
+fn fn_doc(attrs: &[syn::Attribute], sig: &syntax::Signature, ctx: &Context) -> JsDoc {
+    let doc = JsDoc::new(attrs);
+    match &sig.output {
+        syntax::ReturnType::Type(_, ty) if ty.is_result() => doc.error_class(error_class(ty, ctx)),
+        _ => doc,
+    }
+}
+
+/// Create the `Deno.UnsafeCallback` calling the function, function pointers are created by the
+/// caller
+fn expand_callback(
+    out: &mut impl std::fmt::Write,
+    callback: &syntax::TypeCallback,
+    ident: &str,
+    func: &str,
+    non_blocking: bool,
+    indent: &str,
+) -> Result<Utilities, std::fmt::Error> {
+    let mut utilities = Utilities::default();
+
+    if let syntax::CallbackMode::Pointer = callback.mode {
+        writeln!(out, "{}const {} = {}.pointer;", indent, ident, func)?;
+        return Ok(utilities);
+    }
+
+    let mut parameters = vec![];
+    let mut arguments = vec![];
+    let mut values = vec![];
+    for (index, input) in callback.inputs.iter().enumerate() {
+        match input.kind {
+            syntax::TypeKind::Native(ty) => {
+                parameters.push(format!(r#""{}""#, ty.symbol()));
+                arguments.push(format!("__cb{}", index));
+                values.push(format!("__cb{}", index));
+            }
+            _ => {
+                parameters.push(r#""pointer", "usize""#.to_owned());
+                arguments.push(format!("__cb{0}_ptr, __cb{0}_len", index));
+                let buffer = format!(
+                    "Deno.UnsafePointerView.getArrayBuffer(__cb{0}_ptr!, Number(__cb{0}_len))",
+                    index
+                );
+                if let syntax::TypeKind::Json = input.kind {
+                    values.push(format!("__structDecode({})", buffer));
+                    utilities.struct_decode = true;
+                } else {
+                    values.push(format!("__stringDecode({})", buffer));
+                    utilities.string_decode = true;
+                }
+            }
+        }
+    }
+    let result = match &callback.output {
+        Some(syntax::Type {
+            kind: syntax::TypeKind::Native(ty),
+            ..
+        }) => ty.symbol(),
+        _ => "void",
+    };
+
+    // non blocking calls run on another thread
+    let constructor = if non_blocking {
+        "Deno.UnsafeCallback.threadSafe"
+    } else {
+        "new Deno.UnsafeCallback"
+    };
+    writeln!(out, "{}const {} = {}(", indent, ident, constructor)?;
+    writeln!(
+        out,
+        r#"{}  {{ parameters: [{}], result: "{}" }} as const,"#,
+        indent,
+        parameters.join(", "),
+        result
+    )?;
+    writeln!(
+        out,
+        "{}  ({}) => {}({}),",
+        indent,
+        arguments.join(", "),
+        func,
+        values.join(", ")
+    )?;
+    writeln!(out, "{});", indent)?;
 
     Ok(utilities)
 }
@@ -250,28 +1680,89 @@ fn expand_type(out: &mut impl std::fmt::Write, ty: &syntax::Type) -> std::fmt::R
     write!(out, "{}", ty.ts)
 }
 
-fn symbol_type(ty: &syntax::Type) -> &'static str {
-    match ty.kind {
-        syntax::TypeKind::Native(ty) => ty.symbol(),
-        _ => r#"buffer", "usize"#,
+/// The type of an argument or of a result, following the mapping of the 64-bit integers
+fn expand_signature_type(
+    out: &mut impl std::fmt::Write,
+    ty: &syntax::Type,
+    ctx: &Context,
+) -> std::fmt::Result {
+    match ctx.integer_mapping(ty) {
+        Some(Integers::Bigint) => write!(out, "bigint"),
+        Some(Integers::Number) => write!(out, "number"),
+        _ => expand_type(out, ty),
+    }
+}
+
+fn symbol_type(ty: &syntax::Type) -> String {
+    match &ty.kind {
+        syntax::TypeKind::Native(ty) | syntax::TypeKind::NonZero(ty) => {
+            format!(r#""{}""#, ty.symbol())
+        }
+        syntax::TypeKind::Opaque => r#""pointer""#.to_owned(),
+        syntax::TypeKind::Struct(fields) => symbol_struct_type(fields),
+        syntax::TypeKind::Callback(_) => r#""function""#.to_owned(),
+        _ => r#""buffer", "usize""#.to_owned(),
     }
 }
 
-fn symbol_return_type(ty: &syntax::Type) -> &'static str {
-    match ty.kind {
-        syntax::TypeKind::Native(ty) => ty.symbol(),
-        _ => "buffer",
+fn symbol_return_type(ty: &syntax::Type) -> String {
+    match &ty.kind {
+        syntax::TypeKind::Native(ty) | syntax::TypeKind::NonZero(ty) => {
+            format!(r#""{}""#, ty.symbol())
+        }
+        syntax::TypeKind::Opaque => r#""pointer""#.to_owned(),
+        syntax::TypeKind::Struct(fields) => symbol_struct_type(fields),
+        _ => r#""buffer""#.to_owned(),
     }
 }
 
+fn symbol_struct_type(fields: &[syntax::TypeNative]) -> String {
+    let fields = fields
+        .iter()
+        .map(|ty| format!(r#""{}""#, ty.symbol()))
+        .collect::<Vec<_>>();
+    format!(r#"{{ "struct": [{}] }}"#, fields.join(", "))
+}
+
 #[derive(Default)]
 struct Utilities {
     string_encode: bool,
     string_decode: bool,
     struct_encode: bool,
     struct_decode: bool,
+    result_decode: bool,
     len_prefixed_buffer: bool,
+    check_error: bool,
+    int128_encode: bool,
+    int128_decode: bool,
+}
+
+/// The errors are shared by all the modules, they are defined by the library file
+const ERRORS: &str = r#"export class SauroError extends Error {
+  readonly value: unknown;
+
+  constructor(value: unknown, message?: string) {
+    super(message ?? JSON.stringify(value));
+    this.name = "SauroError";
+    this.value = value;
+  }
+}
+
+export class SauroPanic extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = "SauroPanic";
+  }
+}
+
+export class SauroDecodeError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = "SauroDecodeError";
+  }
 }
+"#;
+
 const STRING_ENCODE: &str = r#"function __stringEncode(s: string): ArrayBuffer {
   return new TextEncoder().encode(s);
 }
@@ -287,16 +1778,21 @@ const STRUCT_ENCODE: &str = r#"function __structEncode(v: unknown): ArrayBuffer
 }
 "#;
 
-const STRUCT_DECODE: &str = r#"function __structDecode(v: ArrayBuffer, isResult = false) {
-  if (isResult) {
-    const obj: { Ok?: unknown, Err?: unknown } = JSON.parse(__stringDecode(v));
-    if (obj.Err !== undefined) {
-      throw obj.Err;
-    } else {
-      return obj.Ok;
-    }
+const STRUCT_DECODE: &str = r#"function __structDecode(v: ArrayBuffer) {
+  return JSON.parse(__stringDecode(v));
+}
+"#;
+
+const RESULT_DECODE: &str = r#"function __resultDecode(
+  v: ArrayBuffer,
+  // deno-lint-ignore no-explicit-any
+  error: new (value: any, message?: string) => Error,
+) {
+  const obj: { Ok?: unknown, Err?: unknown, message?: string } = JSON.parse(__stringDecode(v));
+  if ("Err" in obj) {
+    throw new error(obj.Err, obj.message ?? undefined);
   } else {
-    return JSON.parse(__stringDecode(v));
+    return obj.Ok;
   }
 }
 "#;
@@ -316,24 +1812,70 @@ const LEN_PREFIXED_BUFFER: &str = r#"function __lenPrefixedBuffer(v: Deno.Pointe
   const buffer = new ArrayBuffer(len);
   unsafeView.copyInto(buffer, 4);
 
+  // the buffer is owned by the library, release it as soon as it has been copied
+  __symbols.sauro_dealloc(v);
+
   return buffer;
 }
 "#;
 
+const INT128_ENCODE: &str = r#"function __int128Encode(v: bigint, signed: boolean): Uint8Array {
+  if ((signed ? BigInt.asIntN(128, v) : BigInt.asUintN(128, v)) !== v) {
+    throw new RangeError(`${v} is out of the range of ${signed ? "i128" : "u128"}`);
+  }
+
+  const buf = new Uint8Array(16);
+  const view = new DataView(buf.buffer);
+  view.setBigUint64(0, BigInt.asUintN(64, v), true);
+  view.setBigUint64(8, BigInt.asUintN(64, v >> 64n), true);
+  return buf;
+}
+"#;
+
+const INT128_DECODE: &str = r#"function __int128Decode(v: ArrayBuffer, signed: boolean): bigint {
+  const view = new DataView(v);
+  const lo = view.getBigUint64(0, true);
+  const hi = signed ? view.getBigInt64(8, true) : view.getBigUint64(8, true);
+  return (hi << 64n) | lo;
+}
+"#;
+
+const CHECK_ERROR: &str = r#"function __checkError(error: BigUint64Array) {
+  if (error[0] === 0n) {
+    return;
+  }
+
+  const ptr = Deno.UnsafePointer.create(error[0]);
+  const { kind, message }: { kind: string, message: string } = JSON.parse(
+    __stringDecode(__lenPrefixedBuffer(ptr)),
+  );
+  switch (kind) {
+    case "decode":
+      throw new SauroDecodeError(message);
+    default:
+      throw new SauroPanic(message);
+  }
+}
+"#;
+
 impl Utilities {
     fn merge(&mut self, other: Self) {
         self.string_encode |= other.string_encode;
         self.string_decode |= other.string_decode;
         self.struct_encode |= other.struct_encode;
         self.struct_decode |= other.struct_decode;
+        self.result_decode |= other.result_decode;
         self.len_prefixed_buffer |= other.len_prefixed_buffer;
+        self.check_error |= other.check_error;
+        self.int128_encode |= other.int128_encode;
+        self.int128_decode |= other.int128_decode;
     }
 
     fn expand(&self, out: &mut impl std::fmt::Write) -> std::fmt::Result {
         if self.string_encode | self.struct_encode {
             writeln!(out, "{}", STRING_ENCODE)?;
         }
-        if self.string_decode | self.struct_decode {
+        if self.string_decode | self.struct_decode | self.result_decode | self.check_error {
             writeln!(out, "{}", STRING_DECODE)?;
         }
         if self.struct_encode {
@@ -342,16 +1884,32 @@ impl Utilities {
         if self.struct_decode {
             writeln!(out, "{}", STRUCT_DECODE)?;
         }
-        if self.len_prefixed_buffer {
+        if self.result_decode {
+            writeln!(out, "{}", RESULT_DECODE)?;
+        }
+        if self.len_prefixed_buffer | self.check_error {
             writeln!(out, "{}", LEN_PREFIXED_BUFFER)?;
         }
+        if self.check_error {
+            writeln!(out, "{}", CHECK_ERROR)?;
+        }
+        if self.int128_encode {
+            writeln!(out, "{}", INT128_ENCODE)?;
+        }
+        if self.int128_decode {
+            writeln!(out, "{}", INT128_DECODE)?;
+        }
 
         Ok(())
     }
 }
 
-fn is_non_blocking_fn(func: &syntax::ItemFn) -> bool {
-    for attr in &func.attrs {
+fn is_non_blocking_fn(attrs: &[syn::Attribute], sig: &syntax::Signature) -> bool {
+    // the future is driven to completion on a separate thread, never block the event loop
+    if sig.asyncness.is_some() {
+        return true;
+    }
+    for attr in attrs {
         if let syn::Meta::Path(path) = &attr.meta {
             let segments = &path.segments;
             if segments.len() == 2
@@ -364,3 +1922,806 @@ fn is_non_blocking_fn(func: &syntax::ItemFn) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        process::Command,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use sha2::{Digest, Sha256};
+    use syn::parse_quote;
+
+    use super::*;
+    use crate::{platform::Platform, testing::TempDir};
+
+    fn bindings(module_path: &str, module: syn::ItemMod) -> Bindings {
+        let module = syntax::parse_module(module).unwrap();
+        expand_module(&module, module_path, "./", Integers::NumberOrBigint).unwrap()
+    }
+
+    #[test]
+    fn symbols_include_the_module_path() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    pub fn new() -> u32 {
+                        0
+                    }
+                }
+            },
+        );
+        assert!(bindings
+            .source
+            .contains(r#"__symbols["app::api::new"](__error)"#));
+    }
+
+    #[test]
+    fn ambiguous_names_are_exported_by_namespace() {
+        let shapes = bindings(
+            "app::shapes",
+            parse_quote! {
+                mod shapes {
+                    pub fn area(side: f64) -> f64 {
+                        side * side
+                    }
+
+                    pub fn hypot(a: f64, b: f64) -> f64 {
+                        a.hypot(b)
+                    }
+                }
+            },
+        );
+        let space = bindings(
+            "app::geometry::space",
+            parse_quote! {
+                mod space {
+                    pub fn hypot(a: f64, b: f64, c: f64) -> f64 {
+                        (a * a + b * b + c * c).sqrt()
+                    }
+                }
+            },
+        );
+        let units = bindings(
+            "app::units",
+            parse_quote! {
+                mod units {
+                    pub fn to_radians(degrees: f64) -> f64 {
+                        degrees.to_radians()
+                    }
+                }
+            },
+        );
+        let entry_point = expand_entry_point(&[
+            ("shapes.ts", &shapes),
+            ("geometry/space.ts", &space),
+            ("units.ts", &units),
+        ])
+        .unwrap();
+        assert_eq!(
+            entry_point,
+            [
+                r#"export { SauroDecodeError, SauroError, SauroPanic } from "./_library.ts";"#,
+                r#"export { area } from "./shapes.ts";"#,
+                r#"export * as shapes from "./shapes.ts";"#,
+                r#"export * as geometry_space from "./geometry/space.ts";"#,
+                r#"export * from "./units.ts";"#,
+                "",
+            ]
+            .join("\n")
+        );
+
+        let library = expand_library(
+            &[&shapes, &space, &units],
+            &[],
+            &Loader::Local {
+                prefix: "./",
+                search_paths: &[],
+            },
+        )
+        .unwrap();
+        assert!(library.contains(r#""app::shapes::hypot": {"#));
+        assert!(library.contains(r#""app::geometry::space::hypot": {"#));
+    }
+
+    #[test]
+    fn namespace_clashing_with_a_name_is_rejected() {
+        let first = bindings(
+            "app::first",
+            parse_quote! {
+                mod first {
+                    pub fn value() -> u32 {
+                        1
+                    }
+
+                    pub fn second() -> u32 {
+                        2
+                    }
+                }
+            },
+        );
+        let second = bindings(
+            "app::second",
+            parse_quote! {
+                mod second {
+                    pub fn value() -> u32 {
+                        2
+                    }
+                }
+            },
+        );
+        let error =
+            expand_entry_point(&[("first.ts", &first), ("second.ts", &second)]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the namespace second of the bindings of module app::second is already exported"
+        );
+    }
+
+    fn library_source(filename: &str, sha256: &str) -> Vec<u8> {
+        let module = bindings(
+            "my_crate::api",
+            parse_quote! {
+                mod api {
+                    pub fn add(a: i32, b: i32) -> i32 {
+                        a + b
+                    }
+                }
+            },
+        );
+        let libraries =
+            ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"].map(|triple| Library {
+                platform: Platform::from_triple(triple).unwrap(),
+                filename: filename.to_owned(),
+                sha256: sha256.to_owned(),
+            });
+        // the checksums are only written by the remote loader
+        let loader = Loader::Remote {
+            base_url: "https://example.com/v1/",
+        };
+        expand_library(&[&module], &libraries, &loader)
+            .unwrap()
+            .into_bytes()
+    }
+
+    #[test]
+    fn checksums_are_ignored() {
+        let stripped = |filename, sha256| {
+            String::from_utf8(without_checksums(&library_source(filename, sha256))).unwrap()
+        };
+        let source = String::from_utf8(library_source("libmy_crate.so", "0123abcd")).unwrap();
+        assert!(source.contains("sha256: '0123abcd'"));
+        assert!(stripped("libmy_crate.so", "0123abcd").contains("sha256: ''"));
+        assert_eq!(
+            stripped("libmy_crate.so", "0123abcd"),
+            stripped("libmy_crate.so", "4567ef01")
+        );
+        // the other changes of the file are still detected
+        assert_ne!(
+            stripped("libmy_crate.so", "0123abcd"),
+            stripped("libother.so", "0123abcd")
+        );
+    }
+
+    #[test]
+    fn only_checksums_are_stripped() {
+        assert_eq!(
+            without_checksums(b"{ filename: 'a.so', sha256: 'abc' }, { sha256: 'def' }"),
+            b"{ filename: 'a.so', sha256: '' }, { sha256: '' }"
+        );
+        assert_eq!(
+            without_checksums(b"library.sha256 !== sha256"),
+            b"library.sha256 !== sha256"
+        );
+        // an unterminated checksum is stripped up to the end
+        assert_eq!(without_checksums(b"sha256: 'abc"), b"sha256: '");
+    }
+
+    #[test]
+    fn base_url_is_a_string_literal() {
+        let mut source = String::new();
+        expand_remote_loader(&mut source, &[], "https://example.com/`${alert(1)}`").unwrap();
+        assert!(source.contains(r#"const baseUrl = "https://example.com/`${alert(1)}`/";"#));
+        assert!(source.contains("new URL(baseUrl + `${platform}/${library.filename}`"));
+    }
+
+    /// Serve the same content to every request, the number of requests is returned with the URL
+    fn serve(content: &'static [u8]) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(len) => request.extend_from_slice(&buf[..len]),
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content.len()
+                );
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(content);
+            }
+        });
+        (url, requests)
+    }
+
+    /// Run the remote loader with Deno, it prints the path of the loaded library
+    fn load(dir: &TempDir, base_url: &str, library: Library) -> std::process::Output {
+        let mut source = String::new();
+        expand_remote_loader(&mut source, &[library], base_url).unwrap();
+        source.push_str("console.log(__dylib);\n");
+        let script = dir.join("loader.ts");
+        std::fs::write(&script, source).unwrap();
+        Command::new("deno")
+            .args([
+                "run",
+                "--quiet",
+                "--allow-net",
+                "--allow-read",
+                "--allow-write",
+            ])
+            .arg("--allow-env")
+            .arg(&script)
+            .env("SAURO_CACHE_DIR", dir.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn remote_loader() {
+        if Command::new("deno").arg("--version").output().is_err() {
+            eprintln!("deno is not installed, the remote loader is not tested");
+            return;
+        }
+        const CONTENT: &[u8] = b"the content of the library";
+        let (base_url, requests) = serve(CONTENT);
+        let dir = TempDir::new("remote-loader", &[]);
+        let library = |content: &[u8]| Library {
+            platform: Platform::host().unwrap(),
+            filename: "libmy_crate.so".to_owned(),
+            sha256: format!("{:x}", Sha256::digest(content)),
+        };
+
+        // the library is downloaded in the cache
+        let output = load(&dir, &base_url, library(CONTENT));
+        assert!(output.status.success(), "{:?}", output);
+        let path = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(std::fs::read(path.trim()).unwrap(), CONTENT);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // the cached library is not downloaded again
+        let output = load(&dir, &base_url, library(CONTENT));
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), path);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // the downloaded library is not the expected one
+        let output = load(&dir, &base_url, library(b"another library"));
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("checksum mismatch of the library"),
+            "{}",
+            stderr
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    /// The file loading the library, with the symbols of the given bindings
+    fn library(modules: &[&Bindings]) -> String {
+        let loader = Loader::Local {
+            prefix: "./",
+            search_paths: &[],
+        };
+        expand_library(modules, &[], &loader).unwrap()
+    }
+
+    #[test]
+    fn structs_passed_by_value() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    pub struct Point {
+                        pub x: f64,
+                        pub y: f64,
+                        pub id: u8,
+                    }
+
+                    pub struct Mixed(u8, u32, bool, u16, u64);
+
+                    pub fn shift(point: Point, mixed: Mixed) -> Point {
+                        point
+                    }
+                }
+            },
+        );
+        // the struct is aligned to the largest field, the last field is followed by padding
+        let point = [
+            "function __PointEncode(v: Point): Uint8Array {",
+            "  const buf = new Uint8Array(24);",
+            "  const view = new DataView(buf.buffer);",
+            "  view.setFloat64(0, v.x, true);",
+            "  view.setFloat64(8, v.y, true);",
+            "  view.setUint8(16, v.id);",
+            "  return buf;",
+            "}",
+            "",
+            "function __PointDecode(buf: Uint8Array): Point {",
+            "  const view = new DataView(buf.buffer, buf.byteOffset, buf.byteLength);",
+            "  return {",
+            "    x: view.getFloat64(0, true),",
+            "    y: view.getFloat64(8, true),",
+            "    id: view.getUint8(16),",
+            "  };",
+            "}",
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&point), "{}", bindings.source);
+        // every field is aligned to its size
+        let mixed = [
+            "function __MixedEncode(v: Mixed): Uint8Array {",
+            "  const buf = new Uint8Array(24);",
+            "  const view = new DataView(buf.buffer);",
+            "  view.setUint8(0, v[0]);",
+            "  view.setUint32(4, v[1], true);",
+            "  view.setUint8(8, v[2] ? 1 : 0);",
+            "  view.setUint16(10, v[3], true);",
+            "  view.setBigUint64(16, BigInt(v[4]), true);",
+            "  return buf;",
+            "}",
+            "",
+            "function __MixedDecode(buf: Uint8Array): Mixed {",
+            "  const view = new DataView(buf.buffer, buf.byteOffset, buf.byteLength);",
+            "  return [",
+            "    view.getUint8(0),",
+            "    view.getUint32(4, true),",
+            "    view.getUint8(8) !== 0,",
+            "    view.getUint16(10, true),",
+            "    view.getBigUint64(16, true),",
+            "  ];",
+            "}",
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&mixed), "{}", bindings.source);
+        assert!(bindings.source.contains(
+            "  const __arg0 = __PointEncode(point);\n  const __arg1 = __MixedEncode(mixed);\n"
+        ));
+        assert!(bindings
+            .source
+            .contains("  return __PointDecode(__inner_res);\n"));
+
+        let library = library(&[&bindings]);
+        let point = r#"{ "struct": ["f64", "f64", "u8"] }"#;
+        let mixed = r#"{ "struct": ["u8", "u32", "bool", "u16", "u64"] }"#;
+        assert!(library.contains(&format!(
+            r#"      "parameters": [{}, {}, "buffer"],"#,
+            point, mixed
+        )));
+        assert!(library.contains(&format!(r#"      "result": {},"#, point)));
+    }
+
+    #[test]
+    fn callbacks() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    pub fn each(f: impl Fn(&str, Option<u32>) -> bool) {}
+
+                    pub fn order(f: &dyn Fn(u32), p: extern "C" fn(u32) -> u32, name: &str) {}
+
+                    #[sauro::non_blocking]
+                    pub fn later(f: impl Fn(u32)) {}
+
+                    pub async fn wait(f: &mut dyn FnMut(u32)) {}
+                }
+            },
+        );
+
+        // strings and JSON values are passed as a pointer and a length
+        let each = [
+            "export function each(f: (arg0: string, arg1: number | null) => boolean) {",
+            "  const __arg0 = new Deno.UnsafeCallback(",
+            r#"    { parameters: ["pointer", "usize", "pointer", "usize"], result: "bool" } as const,"#,
+            "    (__cb0_ptr, __cb0_len, __cb1_ptr, __cb1_len) => f(__stringDecode(Deno.UnsafePointerView.getArrayBuffer(__cb0_ptr!, Number(__cb0_len))), __structDecode(Deno.UnsafePointerView.getArrayBuffer(__cb1_ptr!, Number(__cb1_len)))),",
+            "  );",
+            "  try {",
+            "    const __error = new BigUint64Array(1);",
+            r#"    const __inner_res = __symbols["app::api::each"](__arg0, __error);"#,
+            "    __checkError(__error);",
+            "  } finally {",
+            "    __arg0.close();",
+            "  }",
+            "}",
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&each), "{}", bindings.source);
+
+        // the callbacks are created once the other arguments are encoded, the function pointers
+        // are owned by the caller and they are not closed
+        let order = [
+            r#"export function order(f: (arg0: number) => void, p: Deno.UnsafeCallback<{ readonly parameters: readonly ["u32"]; readonly result: "u32" }>, name: string) {"#,
+            "  const __arg2_ptr = __stringEncode(name);",
+            "  const __arg2_len = __arg2_ptr.byteLength;",
+            "  const __arg0 = new Deno.UnsafeCallback(",
+            r#"    { parameters: ["u32"], result: "void" } as const,"#,
+            "    (__cb0) => f(__cb0),",
+            "  );",
+            "  const __arg1 = p.pointer;",
+            "  try {",
+            "    const __error = new BigUint64Array(1);",
+            r#"    const __inner_res = __symbols["app::api::order"](__arg0, __arg1, __arg2_ptr, __arg2_len, __error);"#,
+            "    __checkError(__error);",
+            "  } finally {",
+            "    __arg0.close();",
+            "  }",
+            "}",
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&order), "{}", bindings.source);
+
+        // the non blocking functions call the callbacks from another thread
+        for name in ["later", "wait"] {
+            let function = [
+                format!(
+                    "export async function {}(f: (arg0: number) => void) {{",
+                    name
+                ),
+                "  const __arg0 = Deno.UnsafeCallback.threadSafe(".to_owned(),
+                r#"    { parameters: ["u32"], result: "void" } as const,"#.to_owned(),
+                "    (__cb0) => f(__cb0),".to_owned(),
+                "  );".to_owned(),
+                "  try {".to_owned(),
+                "    const __error = new BigUint64Array(1);".to_owned(),
+                format!(
+                    r#"    const __inner_res = await __symbols["app::api::{}"](__arg0, __error);"#,
+                    name
+                ),
+                "    __checkError(__error);".to_owned(),
+                "  } finally {".to_owned(),
+                "    __arg0.close();".to_owned(),
+                "  }".to_owned(),
+                "}".to_owned(),
+            ]
+            .join("\n");
+            assert!(bindings.source.contains(&function), "{}", bindings.source);
+        }
+
+        let library = library(&[&bindings]);
+        let symbols = [
+            ("each", r#"["function", "buffer"]"#, false),
+            (
+                "order",
+                r#"["function", "function", "buffer", "usize", "buffer"]"#,
+                false,
+            ),
+            ("later", r#"["function", "buffer"]"#, true),
+            ("wait", r#"["function", "buffer"]"#, true),
+        ];
+        for (name, parameters, non_blocking) in symbols {
+            let symbol = format!(
+                "    \"app::api::{}\": {{\n      \"parameters\": {},\n      \"result\": \"void\",\n      \"nonblocking\": {},\n",
+                name, parameters, non_blocking
+            );
+            assert!(library.contains(&symbol), "{}", library);
+        }
+    }
+
+    #[test]
+    fn enums() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    #[serde(rename_all = "kebab-case")]
+                    pub enum Role {
+                        Admin,
+                        PowerUser,
+                        #[serde(rename = "anonymous")]
+                        Guest,
+                    }
+
+                    pub enum Shape {
+                        Circle { radius: f64 },
+                        Square(f64),
+                        Pair(f64, f64),
+                        Empty,
+                    }
+
+                    #[serde(tag = "kind", rename_all = "camelCase")]
+                    pub enum Event {
+                        KeyDown { key: String },
+                        Idle,
+                    }
+
+                    #[serde(tag = "t", content = "c")]
+                    pub enum Value {
+                        Number(f64),
+                        Point { x: f64 },
+                        Empty,
+                    }
+
+                    #[serde(tag = "type")]
+                    pub enum LoadError {
+                        Missing,
+                        Corrupted { offset: u32 },
+                    }
+
+                    pub fn draw(role: Role, shape: Shape, event: Event, value: Value) {}
+
+                    pub fn load() -> Result<u32, LoadError> {
+                        Ok(0)
+                    }
+                }
+            },
+        );
+
+        // the unit only enums are unions of string literals
+        let role = r#"export type Role = "admin" | "power-user" | "anonymous";"#;
+        assert!(bindings.source.contains(role), "{}", bindings.source);
+
+        // the enums are externally tagged by default
+        let shape = [
+            "export type Shape =",
+            "  | { Circle: { radius: number } }",
+            "  | { Square: number }",
+            "  | { Pair: [number, number] }",
+            r#"  | "Empty";"#,
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&shape), "{}", bindings.source);
+
+        // the internally tagged enums store the variant name among the fields, even the unit
+        // variants are objects
+        let event = [
+            "export type Event =",
+            r#"  | { kind: "keyDown"; key: string }"#,
+            r#"  | { kind: "idle" };"#,
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&event), "{}", bindings.source);
+
+        // the adjacently tagged enums store the fields apart
+        let value = [
+            "export type Value =",
+            r#"  | { t: "Number"; c: number }"#,
+            r#"  | { t: "Point"; c: { x: number } }"#,
+            r#"  | { t: "Empty" };"#,
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&value), "{}", bindings.source);
+
+        // the kind of the errors is read from the tag
+        let error = [
+            r#"  readonly kind: "Missing" | "Corrupted";"#,
+            r#"  readonly value: { type: "Missing" } | { type: "Corrupted"; offset: number };"#,
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&error), "{}", bindings.source);
+        assert!(bindings.source.contains("    this.kind = value.type;\n"));
+    }
+
+    #[test]
+    fn errors() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    pub enum ParseError {
+                        Empty,
+                        Invalid { position: u32 },
+                    }
+
+                    pub fn parse(s: &str) -> Result<u32, ParseError> {
+                        Ok(0)
+                    }
+
+                    pub fn open(path: &str) -> Result<u32, String> {
+                        Ok(0)
+                    }
+                }
+            },
+        );
+
+        // the errors defined by the module are classes exposing the kind and the value
+        let error = [
+            "export class ParseError extends Error {",
+            r#"  readonly kind: "Empty" | "Invalid";"#,
+            r#"  readonly value: "Empty" | { Invalid: { position: number } };"#,
+            "",
+            r#"  constructor(value: "Empty" | { Invalid: { position: number } }, message?: string) {"#,
+            "    super(message ?? JSON.stringify(value));",
+            r#"    this.name = "ParseError";"#,
+            "    this.value = value;",
+            r#"    this.kind = (typeof value === "string" ? value : Object.keys(value)[0]) as ParseError["kind"];"#,
+            "  }",
+            "}",
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&error), "{}", bindings.source);
+
+        // the other errors are thrown as `SauroError`
+        for (name, class) in [("parse", "ParseError"), ("open", "SauroError")] {
+            let result = [
+                format!(
+                    r#"  const __inner_res = __symbols["app::api::{}"](__arg0_ptr, __arg0_len, __error);"#,
+                    name
+                ),
+                "  __checkError(__error);".to_owned(),
+                format!(
+                    "  return __resultDecode(__lenPrefixedBuffer(__inner_res), {});",
+                    class
+                ),
+            ]
+            .join("\n");
+            assert!(bindings.source.contains(&result), "{}", bindings.source);
+        }
+
+        // the message of the error, when it implements `Display`, is passed to the constructor
+        let library = library(&[&bindings]);
+        assert!(library.contains(ERRORS), "{}", library);
+        assert!(
+            bindings.source.contains(RESULT_DECODE),
+            "{}",
+            bindings.source
+        );
+        assert!(RESULT_DECODE.contains("throw new error(obj.Err, obj.message ?? undefined);"));
+    }
+
+    #[test]
+    fn tuple_and_unit_structs() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    pub struct UserId(u64);
+
+                    pub struct Email(String);
+
+                    pub struct Labeled(String, i32);
+
+                    pub struct Ping;
+
+                    pub fn send(to: UserId, email: Email, label: Labeled, ping: Ping) {}
+                }
+            },
+        );
+
+        // the newtypes are branded, the native ones are passed by value
+        let types = [
+            r#"export type UserId = (number | bigint) & { __brand: "UserId" };"#,
+            r#"export type Email = string & { __brand: "Email" };"#,
+            "export type Labeled = [string, number];",
+            "export type Ping = null;",
+        ];
+        for ty in types {
+            assert!(bindings.source.contains(ty), "{}", bindings.source);
+        }
+        assert!(bindings
+            .source
+            .contains("function __UserIdEncode(v: UserId): Uint8Array {"));
+
+        let library = library(&[&bindings]);
+        let symbol = r#"      "parameters": [{ "struct": ["u64"] }, "buffer", "usize", "buffer", "usize", "buffer", "usize", "buffer"],"#;
+        assert!(library.contains(symbol), "{}", library);
+    }
+
+    #[test]
+    fn serde_attributes() {
+        let bindings = bindings(
+            "app::api",
+            parse_quote! {
+                mod api {
+                    pub struct Location {
+                        city: String,
+                    }
+
+                    #[serde(rename_all = "camelCase")]
+                    pub struct Profile {
+                        user_name: String,
+                        #[serde(rename = "mail")]
+                        email_address: String,
+                        #[serde(default)]
+                        display_name: Option<String>,
+                        #[serde(flatten)]
+                        location: Location,
+                        #[serde(skip)]
+                        visits: u32,
+                    }
+
+                    pub fn save(profile: Profile) {}
+                }
+            },
+        );
+
+        // the properties are named as the JSON ones, the skipped fields are missing, the default
+        // ones are optional and the flattened ones are merged
+        let profile = [
+            "export type Profile = {",
+            "  userName: string;",
+            "  mail: string;",
+            "  displayName?: string | null;",
+            "} & Location",
+        ]
+        .join("\n");
+        assert!(bindings.source.contains(&profile), "{}", bindings.source);
+        assert!(!bindings.source.contains("visits"), "{}", bindings.source);
+    }
+
+    #[test]
+    fn fingerprints_are_checked_by_one_probe() {
+        let shapes = bindings(
+            "app::shapes",
+            parse_quote! {
+                mod shapes {
+                    pub fn area(side: f64) -> f64 {
+                        0.0
+                    }
+                }
+            },
+        );
+        let users = bindings(
+            "app::users",
+            parse_quote! {
+                mod users {
+                    pub fn count() -> u32 {
+                        0
+                    }
+                }
+            },
+        );
+        let library = library(&[&shapes, &users]);
+
+        // the library is opened once to check the fingerprints and once to load the symbols
+        assert_eq!(library.matches("Deno.dlopen(").count(), 2, "{}", library);
+        let probe = [
+            "  const __abi = Deno.dlopen(__dylib, {".to_owned(),
+            format!(
+                r#"    "{}": {{ "parameters": [], "result": "u64" }},"#,
+                abi::symbol("app::shapes")
+            ),
+            format!(
+                r#"    "{}": {{ "parameters": [], "result": "u64" }},"#,
+                abi::symbol("app::users")
+            ),
+            "  });".to_owned(),
+        ]
+        .join("\n");
+        assert!(library.contains(&probe), "{}", library);
+
+        let checks = [
+            "  try {".to_owned(),
+            format!(
+                r#"    __check("app::shapes", BigInt(__abi.symbols["{}"]()), 0x{:016x}n);"#,
+                abi::symbol("app::shapes"),
+                shapes.abi_hash
+            ),
+            format!(
+                r#"    __check("app::users", BigInt(__abi.symbols["{}"]()), 0x{:016x}n);"#,
+                abi::symbol("app::users"),
+                users.abi_hash
+            ),
+            "  } finally {".to_owned(),
+            "    __abi.close();".to_owned(),
+            "  }".to_owned(),
+        ]
+        .join("\n");
+        assert!(library.contains(&checks), "{}", library);
+    }
+}
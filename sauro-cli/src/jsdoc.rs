@@ -0,0 +1,359 @@
+//! Documentation of the bindgen items, emitted as JSDoc comments.
+//!
+//! The `# Arguments` section of the documentation is converted to `@param` tags, the `# Errors`
+//! and `# Panics` sections to `@throws` tags, and the `#[deprecated]` attribute to the
+//! `@deprecated` tag.
+
+#[derive(Default)]
+pub struct JsDoc {
+    description: Vec<String>,
+    params: Vec<(String, String)>,
+    errors: Vec<String>,
+    error_class: Option<String>,
+    panics: Vec<String>,
+    deprecated: Option<String>,
+}
+
+enum Section {
+    Description,
+    Arguments,
+    Errors,
+    Panics,
+}
+
+impl JsDoc {
+    pub fn new(attrs: &[syn::Attribute]) -> Self {
+        let mut doc = JsDoc::default();
+
+        let mut section = Section::Description;
+        let mut code = false;
+        for line in doc_lines(attrs) {
+            if line.trim_start().starts_with("```") {
+                code = !code;
+            } else if code {
+                // hidden lines of the examples
+                if line == "#" || line.starts_with("# ") {
+                    continue;
+                }
+            } else if let Some(heading) = line.strip_prefix('#') {
+                let heading = heading.trim_start_matches('#').trim();
+                section = match heading.to_ascii_lowercase().as_str() {
+                    "arguments" | "parameters" => Section::Arguments,
+                    "errors" => Section::Errors,
+                    "panics" => Section::Panics,
+                    _ => {
+                        doc.description.push(line);
+                        Section::Description
+                    }
+                };
+                continue;
+            }
+            match section {
+                Section::Description => doc.description.push(line),
+                Section::Arguments => doc.push_param(&line),
+                Section::Errors => doc.errors.push(line),
+                Section::Panics => doc.panics.push(line),
+            }
+        }
+        trim_lines(&mut doc.description);
+        trim_lines(&mut doc.errors);
+        trim_lines(&mut doc.panics);
+
+        doc.deprecated = attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("deprecated"))
+            .map(deprecated_note);
+
+        doc
+    }
+
+    /// Set the class of the error thrown by the function, used by the `# Errors` section
+    pub fn error_class(mut self, class: impl Into<String>) -> Self {
+        self.error_class = Some(class.into());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.description.is_empty()
+            && self.params.is_empty()
+            && self.errors.is_empty()
+            && self.panics.is_empty()
+            && self.deprecated.is_none()
+    }
+
+    pub fn write(&self, out: &mut impl std::fmt::Write, indent: &str) -> std::fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut tags = vec![];
+        for (name, description) in &self.params {
+            tags.push(
+                format!("@param {} {}", name, description)
+                    .trim_end()
+                    .to_owned(),
+            );
+        }
+        if !self.errors.is_empty() {
+            let errors = self.errors.join("\n");
+            match &self.error_class {
+                Some(class) => tags.push(format!("@throws {{{}}} {}", class, errors)),
+                None => tags.push(format!("@throws {}", errors)),
+            }
+        }
+        if !self.panics.is_empty() {
+            tags.push(format!("@throws {{SauroPanic}} {}", self.panics.join("\n")));
+        }
+        if let Some(note) = &self.deprecated {
+            tags.push(format!("@deprecated {}", note).trim_end().to_owned());
+        }
+
+        // a single line is written in the short form
+        if self.description.len() + tags.len() == 1 {
+            let line = self.description.first().or(tags.first()).unwrap();
+            if !line.contains('\n') {
+                return writeln!(out, "{}/** {} */", indent, escape(line));
+            }
+        }
+
+        writeln!(out, "{}/**", indent)?;
+        let mut lines = self.description.clone();
+        if !lines.is_empty() && !tags.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(tags.iter().flat_map(|tag| tag.lines().map(str::to_owned)));
+        for line in lines {
+            if line.is_empty() {
+                writeln!(out, "{} *", indent)?;
+            } else {
+                writeln!(out, "{} * {}", indent, escape(&line))?;
+            }
+        }
+        writeln!(out, "{} */", indent)
+    }
+
+    /// Parse an item of the arguments list, e.g. ``* `name` - description``
+    fn push_param(&mut self, line: &str) {
+        let item = line
+            .trim_start()
+            .strip_prefix(['*', '-'])
+            .map(str::trim_start)
+            .and_then(|item| item.strip_prefix('`'))
+            .and_then(|item| item.split_once('`'));
+        match item {
+            Some((name, description)) => {
+                let description = description
+                    .trim_start()
+                    .trim_start_matches(['-', ':'])
+                    .trim();
+                self.params.push((name.to_owned(), description.to_owned()));
+            }
+            // continuation of the previous item
+            None => {
+                if let Some((_, description)) = self.params.last_mut() {
+                    let line = line.trim();
+                    if !line.is_empty() {
+                        description.push(' ');
+                        description.push_str(line);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The lines of the documentation, the leading space added by `///` is removed
+fn doc_lines(attrs: &[syn::Attribute]) -> Vec<String> {
+    let mut lines = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        let syn::Meta::NameValue(meta) = &attr.meta else {
+            continue;
+        };
+        let syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(doc),
+            ..
+        }) = &meta.value
+        else {
+            continue;
+        };
+        // `///` alone is an empty line, which `lines` would skip
+        for line in doc.value().split('\n') {
+            let line = line.strip_prefix(' ').unwrap_or(line);
+            lines.push(line.trim_end().to_owned());
+        }
+    }
+    lines
+}
+
+fn deprecated_note(attr: &syn::Attribute) -> String {
+    match &attr.meta {
+        // #[deprecated = "note"]
+        syn::Meta::NameValue(meta) => match &meta.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(note),
+                ..
+            }) => note.value(),
+            _ => String::new(),
+        },
+        // #[deprecated(since = "..", note = "..")]
+        syn::Meta::List(_) => {
+            let mut note = String::new();
+            let _ = attr.parse_nested_meta(|meta| {
+                let value = meta.value()?.parse::<syn::LitStr>()?;
+                if meta.path.is_ident("note") {
+                    note = value.value();
+                }
+                Ok(())
+            });
+            note
+        }
+        syn::Meta::Path(_) => String::new(),
+    }
+}
+
+/// Remove the empty lines at the beginning and at the end
+fn trim_lines(lines: &mut Vec<String>) {
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    let leading = lines.iter().take_while(|line| line.is_empty()).count();
+    lines.drain(..leading);
+}
+
+/// The comment can not be closed by its content
+fn escape(line: &str) -> String {
+    line.replace("*/", "*\\/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jsdoc(item: syn::ItemFn) -> String {
+        let mut out = String::new();
+        JsDoc::new(&item.attrs).write(&mut out, "  ").unwrap();
+        out
+    }
+
+    #[test]
+    fn empty() {
+        let doc = JsDoc::new(&[]);
+        assert!(doc.is_empty());
+        assert_eq!(jsdoc(syn::parse_quote! { fn f() {} }), "");
+    }
+
+    #[test]
+    fn description() {
+        let out = jsdoc(syn::parse_quote! {
+            /// Add two numbers
+            fn add() {}
+        });
+        assert_eq!(out, "  /** Add two numbers */\n");
+
+        let out = jsdoc(syn::parse_quote! {
+            /// Add two numbers
+            ///
+            /// ```
+            /// # let hidden = 1;
+            /// assert_eq!(add(1, 2), 3);
+            /// ```
+            fn add() {}
+        });
+        assert_eq!(
+            out,
+            "  /**\n   * Add two numbers\n   *\n   * ```\n   * assert_eq!(add(1, 2), 3);\n   * ```\n   */\n"
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let out = jsdoc(syn::parse_quote! {
+            /// Add two numbers
+            ///
+            /// # Arguments
+            ///
+            /// * `a` - The first number
+            /// * `b`: The second number,
+            ///   which is added
+            fn add() {}
+        });
+        assert_eq!(
+            out,
+            "  /**\n   * Add two numbers\n   *\n   * @param a The first number\n   * @param b The second number, which is added\n   */\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let item = syn::parse_quote! {
+            /// # Errors
+            ///
+            /// If the file is missing
+            fn open() {}
+        };
+        assert_eq!(jsdoc(item), "  /** @throws If the file is missing */\n");
+
+        let item: syn::ItemFn = syn::parse_quote! {
+            /// # Errors
+            ///
+            /// If the file is missing
+            fn open() {}
+        };
+        let mut out = String::new();
+        JsDoc::new(&item.attrs)
+            .error_class("OpenError")
+            .write(&mut out, "")
+            .unwrap();
+        assert_eq!(out, "/** @throws {OpenError} If the file is missing */\n");
+    }
+
+    #[test]
+    fn panics() {
+        let out = jsdoc(syn::parse_quote! {
+            /// Divide two numbers
+            ///
+            /// # Panics
+            ///
+            /// If `b` is zero
+            fn div() {}
+        });
+        assert_eq!(
+            out,
+            "  /**\n   * Divide two numbers\n   *\n   * @throws {SauroPanic} If `b` is zero\n   */\n"
+        );
+    }
+
+    #[test]
+    fn deprecated() {
+        let out = jsdoc(syn::parse_quote! {
+            #[deprecated]
+            fn old() {}
+        });
+        assert_eq!(out, "  /** @deprecated */\n");
+
+        let out = jsdoc(syn::parse_quote! {
+            #[deprecated = "use `new` instead"]
+            fn old() {}
+        });
+        assert_eq!(out, "  /** @deprecated use `new` instead */\n");
+
+        let out = jsdoc(syn::parse_quote! {
+            /// The old way
+            #[deprecated(since = "1.0.0", note = "use `new` instead")]
+            fn old() {}
+        });
+        assert_eq!(
+            out,
+            "  /**\n   * The old way\n   *\n   * @deprecated use `new` instead\n   */\n"
+        );
+    }
+
+    #[test]
+    fn escape_end_of_comment() {
+        let out = jsdoc(syn::parse_quote! {
+            /// Match `*/` in a string
+            fn find() {}
+        });
+        assert_eq!(out, "  /** Match `*\\/` in a string */\n");
+    }
+}
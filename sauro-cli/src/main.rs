@@ -1,5 +1,14 @@
 mod cmd;
+mod config;
+mod diagnostic;
 mod expand;
+mod jsdoc;
+mod manifest;
+mod metadata;
+mod platform;
+mod source;
+#[cfg(test)]
+mod testing;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -8,7 +17,9 @@ fn main() -> Result<()> {
     let args = Args::parse();
     match args.command {
         Command::Build(cmd) => cmd.run(),
+        Command::Check(cmd) => cmd.run(),
         Command::New(cmd) => cmd.run(),
+        Command::Watch(cmd) => cmd.run(),
     }
 }
 
@@ -28,5 +39,7 @@ struct Args {
 #[derive(Subcommand)]
 enum Command {
     Build(cmd::BuildCommand),
+    Check(cmd::CheckCommand),
     New(cmd::NewCommand),
+    Watch(cmd::WatchCommand),
 }
@@ -0,0 +1,116 @@
+//! The manifest of a build, written in the directory of the bindings so that the files which
+//! are no longer generated by the next build are removed.
+
+use anyhow::{Context, Result};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+/// The name of the manifest, written in the directory of the bindings
+pub const MANIFEST_FILENAME: &str = ".sauro-manifest.json";
+
+/// The files generated by a build, relative to the directory of the bindings
+#[derive(Default, Deserialize, Serialize)]
+pub struct Manifest {
+    pub files: Vec<String>,
+}
+
+impl Manifest {
+    /// The manifest of the previous build, if any
+    pub fn read(path: &Utf8Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Manifest::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        let manifest = serde_json::from_str::<Manifest>(&content)
+            .with_context(|| format!("invalid manifest {}", path))?;
+        Ok(manifest)
+    }
+
+    /// The files of the manifest which are no longer generated. The manifest can be edited, so
+    /// only the existing files inside `root` are returned, even through a symbolic link.
+    pub fn stale(
+        &self,
+        root: &Utf8Path,
+        is_generated: impl Fn(&Utf8Path) -> bool,
+    ) -> Vec<Utf8PathBuf> {
+        let Ok(root) = root.canonicalize_utf8() else {
+            return vec![];
+        };
+        self.files
+            .iter()
+            .map(Utf8Path::new)
+            .filter(|filename| {
+                filename
+                    .components()
+                    .all(|component| matches!(component, Utf8Component::Normal(_)))
+            })
+            .filter(|filename| !is_generated(filename))
+            .filter(|filename| {
+                root.join(filename)
+                    .canonicalize_utf8()
+                    .is_ok_and(|path| path.starts_with(&root) && path.is_file())
+            })
+            .map(Utf8Path::to_owned)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    /// A directory of bindings next to a file which is not generated by the build
+    fn output(name: &str) -> TempDir {
+        let files = ["bindings/mod.ts", "bindings/api/old.ts", "secret.txt"].map(|file| (file, ""));
+        TempDir::new(&format!("manifest-{}", name), &files)
+    }
+
+    fn manifest(files: &[&str]) -> Manifest {
+        Manifest {
+            files: files.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn stale_files() {
+        let output = output("stale");
+        let manifest = manifest(&["mod.ts", "api/old.ts", "api/missing.ts"]);
+        let stale = manifest.stale(&output.join("bindings"), |filename| filename == "mod.ts");
+        assert_eq!(stale, [Utf8PathBuf::from("api/old.ts")]);
+    }
+
+    #[test]
+    fn files_outside_of_the_root() {
+        let output = output("outside");
+        let secret = output.join("secret.txt");
+        let manifest = manifest(&[
+            "../secret.txt",
+            "api/../../secret.txt",
+            "./../secret.txt",
+            secret.as_str(),
+        ]);
+        let stale = manifest.stale(&output.join("bindings"), |_| false);
+        assert!(stale.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symbolic_links_outside_of_the_root() {
+        let output = output("symlink");
+        std::os::unix::fs::symlink(&*output, output.join("bindings/link")).unwrap();
+        let manifest = manifest(&["link/secret.txt"]);
+        let stale = manifest.stale(&output.join("bindings"), |_| false);
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn missing_root() {
+        let output = output("missing");
+        let manifest = manifest(&["mod.ts"]);
+        assert!(manifest
+            .stale(&output.join("missing"), |_| false)
+            .is_empty());
+    }
+}
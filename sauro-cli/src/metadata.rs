@@ -0,0 +1,222 @@
+//! Description of the bindgen modules embedded by the macro in the compiled library.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use camino::Utf8Path;
+use object::{Object, ObjectSection};
+use sauro_core::syntax;
+
+/// Name of the link section containing the metadata (`__DATA,__sauro` on Apple platforms).
+const SECTION_NAMES: [&str; 2] = [".sauro", "__sauro"];
+
+/// A bindgen module, rebuilt from the metadata of the library.
+pub struct ModuleMetadata {
+    /// The source file containing the module, as returned by `file!()`
+    pub file: String,
+    /// The module path, as returned by `module_path!()`
+    pub module_path: String,
+    pub module: syntax::Module,
+}
+
+/// Read the bindgen modules compiled in the given library.
+pub fn read(dylib: &Utf8Path) -> Result<Vec<ModuleMetadata>> {
+    let data = std::fs::read(dylib)?;
+    let file = object::File::parse(&*data)?;
+
+    let Some(section) = SECTION_NAMES
+        .iter()
+        .find_map(|name| file.section_by_name(name))
+    else {
+        return Ok(vec![]);
+    };
+    let data = section.data()?;
+
+    let mut modules = BTreeMap::<_, Vec<_>>::new();
+    for record in Records(data) {
+        let [file, module_path, index, item] = record?;
+        let index = index.parse::<usize>()?;
+        let item = syn::parse_str::<syn::Item>(item)?;
+        modules
+            .entry((file.to_owned(), module_path.to_owned()))
+            .or_default()
+            .push((index, item));
+    }
+
+    modules
+        .into_iter()
+        .map(|((file, module_path), mut items)| {
+            items.sort_by_key(|(index, _)| *index);
+            let items = items.into_iter().map(|(_, item)| item);
+
+            let name = module_path.rsplit("::").next().unwrap();
+            let ident = syn::Ident::new(name, proc_macro2::Span::call_site());
+            let item_mod: syn::ItemMod = syn::parse_quote!(mod #ident { #(#items)* });
+            let module = syntax::parse_module(item_mod)?;
+
+            Ok(ModuleMetadata {
+                file,
+                module_path,
+                module,
+            })
+        })
+        .collect()
+}
+
+/// Iterator over the records stored in the metadata section.
+struct Records<'a>(&'a [u8]);
+
+impl<'a> Records<'a> {
+    /// Read a record at the start of the data, it returns the parts and the remaining data
+    fn read_record(data: &'a [u8]) -> Result<([&'a str; 4], &'a [u8])> {
+        let (len, rest) = data
+            .split_first_chunk::<4>()
+            .ok_or_else(|| anyhow!("truncated metadata record"))?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len {
+            anyhow::bail!("truncated metadata record");
+        }
+        let (mut record, rest) = rest.split_at(len);
+
+        let mut parts = ["", "", "", ""];
+        for part in &mut parts {
+            *part = Self::read_part(&mut record)?;
+        }
+        if !record.is_empty() {
+            anyhow::bail!("invalid metadata record");
+        }
+        Ok((parts, rest))
+    }
+
+    fn read_part(data: &mut &'a [u8]) -> Result<&'a str> {
+        let (len, rest) = data
+            .split_first_chunk::<4>()
+            .ok_or_else(|| anyhow!("truncated metadata record"))?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len {
+            anyhow::bail!("truncated metadata record");
+        }
+        let (part, rest) = rest.split_at(len);
+        *data = rest;
+        Ok(std::str::from_utf8(part)?)
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<[&'a str; 4]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the linker can add some padding between the records, the length of a record starts with
+        // up to 3 zero bytes too, so the record could start at any of the last 4 zero bytes
+        let zeros = self.0.iter().take_while(|byte| **byte == 0).count();
+        if zeros == self.0.len() {
+            self.0 = &[];
+            return None;
+        }
+        let first = zeros.saturating_sub(3);
+        let mut result = Self::read_record(&self.0[first..]);
+        for start in first + 1..=zeros {
+            if result.is_ok() {
+                break;
+            }
+            if let Ok(record) = Self::read_record(&self.0[start..]) {
+                result = Ok(record);
+            }
+        }
+        match result {
+            Ok((parts, rest)) => {
+                self.0 = rest;
+                Some(Ok(parts))
+            }
+            Err(err) => {
+                self.0 = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A record in the format written by `sauro::ffi::metadata`
+    fn record(parts: [&str; 4]) -> Vec<u8> {
+        let mut content = vec![];
+        for part in parts {
+            content.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            content.extend_from_slice(part.as_bytes());
+        }
+        let mut record = (content.len() as u32).to_be_bytes().to_vec();
+        record.extend(content);
+        record
+    }
+
+    fn read(data: &[u8]) -> Result<Vec<[&str; 4]>> {
+        Records(data).collect()
+    }
+
+    const FIRST: [&str; 4] = ["src/lib.rs", "app::deno", "0", "pub fn f() {}"];
+    const SECOND: [&str; 4] = ["src/lib.rs", "app::deno", "1", "pub struct S;"];
+
+    #[test]
+    fn several_records() {
+        let mut data = record(FIRST);
+        data.extend(record(SECOND));
+        assert_eq!(read(&data).unwrap(), [FIRST, SECOND]);
+    }
+
+    #[test]
+    fn padding_between_records() {
+        // a long record, the first 2 bytes of its length are zero
+        let item = format!("pub fn f() {{ {} }}", "1;".repeat(200));
+        let long = ["src/lib.rs", "app::deno", "2", item.as_str()];
+        for padding in 0..=8 {
+            let mut data = vec![0; padding];
+            data.extend(record(FIRST));
+            data.extend(vec![0; padding]);
+            data.extend(record(long));
+            data.extend(vec![0; padding]);
+            data.extend(record(SECOND));
+            data.extend(vec![0; padding]);
+            assert_eq!(read(&data).unwrap(), [FIRST, long, SECOND]);
+        }
+    }
+
+    #[test]
+    fn empty_section() {
+        assert!(read(&[]).unwrap().is_empty());
+        assert!(read(&[0; 16]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated_record() {
+        let first = record(FIRST);
+        let mut data = first.clone();
+        data.extend(record(SECOND));
+        for len in 1..data.len() {
+            let data = &data[..len];
+            let (complete, rest) = if len < first.len() {
+                (vec![], data)
+            } else {
+                (vec![FIRST], &data[first.len()..])
+            };
+            // the zero bytes at the start of a length can not be distinguished from padding
+            if rest.iter().all(|byte| *byte == 0) {
+                assert_eq!(read(data).unwrap(), complete);
+            } else {
+                let error = read(data).unwrap_err();
+                assert_eq!(error.to_string(), "truncated metadata record");
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_part() {
+        let mut data = record(FIRST);
+        // the last part is longer than the record
+        let len = data.len();
+        data[len - FIRST[3].len() - 1] += 1;
+        assert!(read(&data).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Platforms of the compiled libraries, identified as in `Deno.build.os` and `Deno.build.arch`.
+
+use anyhow::{bail, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Platform {
+    /// The operating system, as in `Deno.build.os`
+    pub os: &'static str,
+    /// The architecture, as in `Deno.build.arch`
+    pub arch: &'static str,
+}
+
+/// A library compiled for a platform
+pub struct Library {
+    pub platform: Platform,
+    /// The file name of the library
+    pub filename: String,
+    /// The SHA-256 checksum of the library, hex encoded
+    pub sha256: String,
+}
+
+/// How the bindings find the libraries
+pub enum Loader<'a> {
+    /// The libraries are next to the bindings, the prefix is relative to the bindings file. The
+    /// search paths are used when the library is missing.
+    Local {
+        prefix: &'a str,
+        search_paths: &'a [String],
+    },
+    /// The libraries are downloaded from the base URL into the cache, the base URL can be
+    /// relative to the bindings file
+    Remote { base_url: &'a str },
+}
+
+impl Platform {
+    /// The platform of the host.
+    pub fn host() -> Result<Self> {
+        let os = match std::env::consts::OS {
+            "macos" => "darwin",
+            os => os,
+        };
+        Self::new(os, std::env::consts::ARCH)
+    }
+
+    /// The platform of a target triple (e.g. `aarch64-apple-darwin`).
+    pub fn from_triple(triple: &str) -> Result<Self> {
+        let Some((arch, system)) = triple.split_once('-') else {
+            bail!("invalid target triple {}", triple);
+        };
+        // the layout of the structs passed by value assumes 64-bit pointers
+        if system.ends_with("gnux32") || system.contains("ilp32") {
+            bail!("target {} with 32-bit pointers is not supported", triple);
+        }
+        let os = if system.contains("darwin") {
+            "darwin"
+        } else if system.contains("android") {
+            "android"
+        } else if system.contains("linux") {
+            "linux"
+        } else if system.contains("windows") {
+            "windows"
+        } else {
+            system.rsplit('-').next().unwrap_or(system)
+        };
+        Self::new(os, arch)
+    }
+
+    fn new(os: &str, arch: &str) -> Result<Self> {
+        let os = match os {
+            "darwin" => "darwin",
+            "linux" => "linux",
+            "android" => "android",
+            "windows" => "windows",
+            "freebsd" => "freebsd",
+            "netbsd" => "netbsd",
+            "aix" => "aix",
+            "solaris" => "solaris",
+            "illumos" => "illumos",
+            _ => bail!("operating system {} is not supported by Deno", os),
+        };
+        let arch = match arch {
+            "x86_64" => "x86_64",
+            "aarch64" => "aarch64",
+            _ => bail!("architecture {} is not supported by Deno", arch),
+        };
+        Ok(Self { os, arch })
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.os, self.arch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_triple() {
+        let cases = [
+            ("x86_64-unknown-linux-gnu", "linux-x86_64"),
+            ("x86_64-unknown-linux-musl", "linux-x86_64"),
+            ("aarch64-unknown-linux-gnu", "linux-aarch64"),
+            ("aarch64-apple-darwin", "darwin-aarch64"),
+            ("x86_64-apple-darwin", "darwin-x86_64"),
+            ("x86_64-pc-windows-msvc", "windows-x86_64"),
+            ("x86_64-pc-windows-gnu", "windows-x86_64"),
+            ("aarch64-linux-android", "android-aarch64"),
+            ("x86_64-unknown-freebsd", "freebsd-x86_64"),
+            ("x86_64-unknown-illumos", "illumos-x86_64"),
+        ];
+        for (triple, platform) in cases {
+            assert_eq!(Platform::from_triple(triple).unwrap().to_string(), platform);
+        }
+    }
+
+    #[test]
+    fn unsupported_triples() {
+        let cases = [
+            ("wasm32", "invalid target triple wasm32"),
+            (
+                "i686-unknown-linux-gnu",
+                "architecture i686 is not supported by Deno",
+            ),
+            (
+                "riscv64gc-unknown-linux-gnu",
+                "architecture riscv64gc is not supported by Deno",
+            ),
+            (
+                "x86_64-unknown-redox",
+                "operating system redox is not supported by Deno",
+            ),
+            (
+                "x86_64-unknown-linux-gnux32",
+                "target x86_64-unknown-linux-gnux32 with 32-bit pointers is not supported",
+            ),
+            (
+                "aarch64-unknown-linux-gnu_ilp32",
+                "target aarch64-unknown-linux-gnu_ilp32 with 32-bit pointers is not supported",
+            ),
+        ];
+        for (triple, message) in cases {
+            let error = Platform::from_triple(triple).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Discovery of the bindgen modules in the sources of a crate, without compiling it.
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// A module annotated with `#[sauro::bindgen]`
+pub struct BindgenModule {
+    /// The source file containing the module
+    pub file: Utf8PathBuf,
+    pub item: syn::ItemMod,
+}
+
+/// A source file which can not be parsed
+pub struct ParseError {
+    pub file: Utf8PathBuf,
+    pub error: syn::Error,
+}
+
+/// Find the bindgen modules in the module tree of the crate, starting from its root file
+/// (e.g. `src/lib.rs`).
+pub fn find_modules(root: &Utf8Path) -> Result<(Vec<BindgenModule>, Vec<ParseError>)> {
+    let mut finder = Finder::default();
+    let dir = root.parent().unwrap_or(Utf8Path::new("."));
+    finder.visit_file(root, dir)?;
+    Ok((finder.modules, finder.errors))
+}
+
+#[derive(Default)]
+struct Finder {
+    modules: Vec<BindgenModule>,
+    errors: Vec<ParseError>,
+}
+
+impl Finder {
+    /// Visit a source file, the submodules declared with `mod foo;` are searched in `dir`
+    fn visit_file(&mut self, file: &Utf8Path, dir: &Utf8Path) -> Result<()> {
+        let content = std::fs::read_to_string(file)?;
+        match syn::parse_file(&content) {
+            Ok(ast) => self.visit_items(ast.items, file, dir),
+            Err(error) => {
+                self.errors.push(ParseError {
+                    file: file.to_owned(),
+                    error,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn visit_items(
+        &mut self,
+        items: Vec<syn::Item>,
+        file: &Utf8Path,
+        dir: &Utf8Path,
+    ) -> Result<()> {
+        for item in items {
+            let syn::Item::Mod(item) = item else {
+                continue;
+            };
+            if is_bindgen(&item) {
+                self.modules.push(BindgenModule {
+                    file: file.to_owned(),
+                    item,
+                });
+                continue;
+            }
+
+            let name = item.ident.to_string();
+            let path = path_attr(&item);
+            match item.content {
+                Some((_, items)) => {
+                    let dir = match path {
+                        Some(path) => dir.join(path),
+                        None => dir.join(&name),
+                    };
+                    self.visit_items(items, file, &dir)?;
+                }
+                None => {
+                    // `#[path]` is relative to the directory of the current file
+                    let candidates = match path {
+                        Some(path) => vec![file.parent().unwrap_or(dir).join(path)],
+                        None => vec![
+                            dir.join(format!("{}.rs", name)),
+                            dir.join(&name).join("mod.rs"),
+                        ],
+                    };
+                    // the missing files are ignored, they could be disabled by a `cfg`
+                    if let Some(child) = candidates.into_iter().find(|file| file.is_file()) {
+                        let child_dir = if child.file_name() == Some("mod.rs") {
+                            child.parent().unwrap().to_owned()
+                        } else {
+                            child.with_extension("")
+                        };
+                        self.visit_file(&child, &child_dir)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The module is annotated with `#[sauro::bindgen]` or `#[bindgen]`
+fn is_bindgen(item: &syn::ItemMod) -> bool {
+    item.attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "bindgen")
+    })
+}
+
+/// The value of the `#[path = "..."]` attribute
+fn path_attr(item: &syn::ItemMod) -> Option<String> {
+    item.attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("path"))
+        .find_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(path),
+                        ..
+                    }),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    fn modules(krate: &Utf8Path) -> Vec<(String, Utf8PathBuf)> {
+        let (modules, errors) = find_modules(&krate.join("src/lib.rs")).unwrap();
+        assert!(errors.is_empty());
+        modules
+            .into_iter()
+            .map(|module| {
+                let file = module.file.strip_prefix(krate).unwrap().to_owned();
+                (module.item.ident.to_string(), file)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inline_modules() {
+        let krate = TempDir::new(
+            "source-inline",
+            &[(
+                "src/lib.rs",
+                "#[sauro::bindgen]\nmod deno {}\nmod api {\n    #[bindgen]\n    pub mod v1 {}\n}\n",
+            )],
+        );
+        assert_eq!(
+            modules(&krate),
+            [
+                ("deno".to_owned(), Utf8PathBuf::from("src/lib.rs")),
+                ("v1".to_owned(), Utf8PathBuf::from("src/lib.rs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_files() {
+        let krate = TempDir::new(
+            "source-nested",
+            &[
+                ("src/lib.rs", "mod api;\nmod util;\nmod disabled;\n"),
+                ("src/api/mod.rs", "mod v1;\n"),
+                ("src/api/v1.rs", "mod inner {\n    mod deno;\n}\n"),
+                (
+                    "src/api/v1/inner/deno.rs",
+                    "#[sauro::bindgen]\nmod bindings {}\n",
+                ),
+                ("src/util.rs", "#[path = \"other.rs\"]\nmod renamed;\n"),
+                ("src/other.rs", "#[sauro::bindgen]\nmod other {}\n"),
+            ],
+        );
+        assert_eq!(
+            modules(&krate),
+            [
+                (
+                    "bindings".to_owned(),
+                    Utf8PathBuf::from("src/api/v1/inner/deno.rs")
+                ),
+                ("other".to_owned(), Utf8PathBuf::from("src/other.rs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let krate = TempDir::new(
+            "source-errors",
+            &[
+                (
+                    "src/lib.rs",
+                    "mod broken;\n#[sauro::bindgen]\nmod deno {}\n",
+                ),
+                ("src/broken.rs", "fn f( {}\n"),
+            ],
+        );
+        let (modules, errors) = find_modules(&krate.join("src/lib.rs")).unwrap();
+        assert_eq!(modules.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, krate.join("src/broken.rs"));
+    }
+}
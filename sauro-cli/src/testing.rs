@@ -0,0 +1,37 @@
+//! Helpers shared by the tests.
+
+use std::ops::Deref;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// A temporary directory, removed when dropped
+pub struct TempDir(Utf8PathBuf);
+
+impl TempDir {
+    /// Create the directory with the given files, the name must be unique among the tests
+    pub fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let dir = std::env::temp_dir().join(format!("sauro-{}-{}", name, std::process::id()));
+        let dir = TempDir(Utf8PathBuf::try_from(dir).unwrap());
+        std::fs::create_dir_all(&dir.0).unwrap();
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Utf8Path;
+
+    fn deref(&self) -> &Utf8Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
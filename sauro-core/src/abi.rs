@@ -0,0 +1,184 @@
+//! Fingerprint of the interface exported by a bindgen module.
+//!
+//! The fingerprint is the 64 bits FNV-1a hash of the item hashes, each item is hashed using the
+//! parts of its definition which affect the generated bindings (signatures, types and fields),
+//! the layout of the structs passed by value is added too.
+//! The library exports a function returning the fingerprint, the bindings compare it with the
+//! value computed when they were generated.
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{Attribute, Ident};
+
+use crate::syntax::{Fields, Item, ItemStruct, Layout, Module, Signature};
+
+const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const PRIME: u64 = 0x100000001b3;
+
+/// The prefix of the symbol of the function returning the fingerprint, followed by the path of the
+/// module (e.g. `__sauro_abi_my_crate::foo::api`), so that modules with the same name do not clash.
+pub const SYMBOL_PREFIX: &str = "__sauro_abi_";
+
+/// The symbol of the function returning the fingerprint of the module.
+pub fn symbol(module_path: &str) -> String {
+    format!("{}{}", SYMBOL_PREFIX, module_path)
+}
+
+/// The fingerprint of the module.
+pub fn module_hash(module: &Module) -> u64 {
+    module
+        .items
+        .iter()
+        .flat_map(item_values)
+        .fold(init(), combine)
+}
+
+/// The initial value of the fingerprint.
+pub fn init() -> u64 {
+    OFFSET_BASIS
+}
+
+/// Add the hash of an item to the fingerprint.
+pub fn combine(hash: u64, item_hash: u64) -> u64 {
+    fnv1a(hash, &item_hash.to_le_bytes())
+}
+
+/// The expression adding a value to the fingerprint `hash`, `sauro::ffi::abi_combine` is tested
+/// to be equal to [`combine`].
+pub fn combine_expr(hash: &Ident, value: impl ToTokens) -> TokenStream {
+    quote!(::sauro::ffi::abi_combine(#hash, #value))
+}
+
+/// The values added to the fingerprint by an item: the hash of its definition, followed by the
+/// size, the alignment and the offsets of the fields of a struct passed by value. The library
+/// evaluates the layout of the compiled struct (see [`item_exprs`]), so that the fingerprints
+/// differ if it is not the layout used by the bindings.
+pub fn item_values(item: &Item) -> Vec<u64> {
+    let mut values = vec![item_hash(item)];
+    if let Some(fields) = native_struct(item).and_then(|item| item.native_fields()) {
+        let layout = Layout::new(&fields);
+        values.extend(
+            [layout.size, layout.align]
+                .into_iter()
+                .chain(layout.offsets)
+                .map(|value| value as u64),
+        );
+    }
+    values
+}
+
+/// The expressions evaluated by the library, one for each value of [`item_values`].
+pub fn item_exprs(item: &Item) -> Vec<TokenStream> {
+    let item_hash = item_hash(item);
+    let mut exprs = vec![quote!(#item_hash)];
+    if let Some(item) = native_struct(item) {
+        let ident = &item.ident;
+        exprs.push(quote!(::std::mem::size_of::<#ident>() as u64));
+        exprs.push(quote!(::std::mem::align_of::<#ident>() as u64));
+        exprs.extend(item.fields.iter().enumerate().map(|(index, field)| {
+            let member = match &field.ident {
+                Some(ident) => ident.to_token_stream(),
+                None => syn::Index::from(index).to_token_stream(),
+            };
+            quote!(::std::mem::offset_of!(#ident, #member) as u64)
+        }));
+    }
+    exprs
+}
+
+/// The struct, if it is passed by value
+fn native_struct(item: &Item) -> Option<&ItemStruct> {
+    match item {
+        Item::Struct(item) if item.native_fields().is_some() => Some(item),
+        _ => None,
+    }
+}
+
+/// The hash of an item.
+pub fn item_hash(item: &Item) -> u64 {
+    let description = match item {
+        Item::Enum(item) => {
+            let attrs = abi_attrs(&item.attrs);
+            let ident = &item.ident;
+            let variants = item.variants.iter().map(|variant| {
+                let attrs = abi_attrs(&variant.attrs);
+                let ident = &variant.ident;
+                let fields = abi_fields(&variant.fields);
+                quote!(#(#attrs)* #ident #fields)
+            });
+            quote!(#(#attrs)* enum #ident { #(#variants),* })
+        }
+        Item::Fn(item) => abi_signature(&item.sig, None),
+        Item::Impl(item) => {
+            let self_ty = &item.self_ty;
+            let methods = item
+                .items
+                .iter()
+                .map(|method| abi_signature(&method.sig, Some(self_ty)));
+            quote!(impl #self_ty { #(#methods;)* })
+        }
+        Item::Opaque(item) => {
+            let ident = &item.item.ident;
+            quote!(struct #ident;)
+        }
+        Item::Struct(item) => {
+            let attrs = abi_attrs(&item.attrs);
+            let ident = &item.ident;
+            let fields = abi_fields(&item.fields);
+            quote!(#(#attrs)* struct #ident #fields)
+        }
+    };
+    hash_tokens(OFFSET_BASIS, description)
+}
+
+/// Hash the tokens, the string representation of a token stream is not used because it depends on
+/// the implementation (compiler or fallback).
+fn hash_tokens(hash: u64, tokens: TokenStream) -> u64 {
+    tokens.into_iter().fold(hash, |hash, token| match token {
+        TokenTree::Group(group) => {
+            let (open, close) = match group.delimiter() {
+                Delimiter::Parenthesis => ("(", ")"),
+                Delimiter::Brace => ("{", "}"),
+                Delimiter::Bracket => ("[", "]"),
+                Delimiter::None => ("", ""),
+            };
+            let hash = fnv1a(hash, open.as_bytes());
+            let hash = hash_tokens(hash, group.stream());
+            fnv1a(hash, close.as_bytes())
+        }
+        TokenTree::Ident(ident) => fnv1a(fnv1a(hash, ident.to_string().as_bytes()), b" "),
+        TokenTree::Punct(punct) => fnv1a(hash, punct.as_char().to_string().as_bytes()),
+        TokenTree::Literal(literal) => fnv1a(fnv1a(hash, literal.to_string().as_bytes()), b" "),
+    })
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash = (hash ^ *byte as u64).wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// The attributes changing the bindings, documentation is ignored.
+fn abi_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| !attr.path().is_ident("doc"))
+}
+
+fn abi_fields(fields: &Fields) -> TokenStream {
+    let fields = fields.iter().map(|field| {
+        let attrs = abi_attrs(&field.attrs);
+        let ident = &field.ident;
+        let ty = &field.ty;
+        quote!(#(#attrs)* #ident : #ty)
+    });
+    quote!((#(#fields),*))
+}
+
+fn abi_signature(sig: &Signature, self_ty: Option<&Ident>) -> TokenStream {
+    let asyncness = &sig.asyncness;
+    let ident = &sig.ident;
+    let receiver = sig.receiver.as_ref().map(|_| quote!(self: *mut #self_ty,));
+    let inputs = sig.inputs.iter().map(|input| &input.ty);
+    let output = sig.output.to_token_stream();
+    quote!(#asyncness fn #ident (#receiver #(#inputs),*) #output)
+}
@@ -1,9 +1,14 @@
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote, quote_spanned, ToTokens};
-use syn::spanned::Spanned;
-
-use crate::syntax::{
-    Field, FnArg, Item, ItemFn, ItemStruct, Module, ReturnType, Type, TypeKind, TypeNative,
+use syn::{spanned::Spanned, Attribute, Ident, Token};
+
+use crate::{
+    abi,
+    syntax::{
+        CallbackMode, Field, Fields, FnArg, ImplItemFn, Item, ItemEnum, ItemFn, ItemImpl,
+        ItemOpaque, ItemStruct, Metadata, Module, Receiver, ReturnType, Signature, Type,
+        TypeCallback, TypeKind, TypeNative, Variant,
+    },
 };
 
 pub fn bindgen(input: Module) -> TokenStream {
@@ -14,11 +19,21 @@ pub fn bindgen(input: Module) -> TokenStream {
     let ident = input.ident;
 
     let span = input.brace_token.span;
+    let abi = AbiFn {
+        items: &input.items,
+        metadata: &input.metadata,
+    }
+    .into_token_stream();
     let items = input
         .items
         .into_iter()
         .map(quote::ToTokens::into_token_stream);
-    let expanded = quote_spanned!(span => {#(#items)*});
+    let metadata = input
+        .metadata
+        .iter()
+        .enumerate()
+        .map(|(index, metadata)| MetadataStatic { index, metadata });
+    let expanded = quote_spanned!(span => {#(#items)* #(#metadata)* #abi});
 
     quote! {
         #attrs
@@ -26,10 +41,82 @@ pub fn bindgen(input: Module) -> TokenStream {
     }
 }
 
+/// The exported function returning the fingerprint of the module, see [`abi`]
+struct AbiFn<'a> {
+    items: &'a [Item],
+    metadata: &'a [Metadata],
+}
+
+impl<'a> ToTokens for AbiFn<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let prefix = abi::SYMBOL_PREFIX;
+        let hash = format_ident!("__hash");
+        let init = abi::init();
+        let combine = self
+            .items
+            .iter()
+            .zip(self.metadata)
+            .map(|(item, metadata)| {
+                let cfg = &metadata.cfg;
+                let exprs = abi::item_exprs(item)
+                    .into_iter()
+                    .map(|value| abi::combine_expr(&hash, value));
+                quote! {
+                    #(#cfg)*
+                    let #hash = {
+                        #(let #hash = #exprs;)*
+                        #hash
+                    };
+                }
+            });
+
+        tokens.extend(quote! {
+            #[export_name = ::std::concat!(#prefix, ::std::module_path!())]
+            pub extern "C" fn __sauro_abi() -> u64 {
+                let #hash: u64 = #init;
+                #(#combine)*
+                #hash
+            }
+        })
+    }
+}
+
+/// The static containing the description of an item, stored in a dedicated link section
+struct MetadataStatic<'a> {
+    index: usize,
+    metadata: &'a Metadata,
+}
+
+impl<'a> ToTokens for MetadataStatic<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let cfg = &self.metadata.cfg;
+        let index = self.index.to_string();
+        let item = self.metadata.item.to_token_stream().to_string();
+
+        tokens.extend(quote! {
+            #(#cfg)*
+            const _: () = {
+                const RECORD: &[&str] = &[::std::file!(), ::std::module_path!(), #index, #item];
+
+                // the path of the module makes the symbol unique, as for the fingerprint
+                #[used]
+                #[export_name = ::std::concat!("__sauro_metadata_", ::std::module_path!(), "_", #index)]
+                #[cfg_attr(target_vendor = "apple", link_section = "__DATA,__sauro")]
+                #[cfg_attr(not(target_vendor = "apple"), link_section = ".sauro")]
+                static METADATA: [u8; ::sauro::ffi::metadata_len(RECORD)] =
+                    ::sauro::ffi::metadata(RECORD);
+            };
+        })
+    }
+}
+
 impl quote::ToTokens for Item {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match &self {
+            Item::Enum(input) => input.to_tokens(tokens),
             Item::Fn(input) => input.to_tokens(tokens),
+            Item::Impl(input) => input.to_tokens(tokens),
+            Item::Opaque(input) => input.to_tokens(tokens),
             Item::Struct(input) => input.to_tokens(tokens),
         }
     }
@@ -41,25 +128,242 @@ impl quote::ToTokens for ItemStruct {
         let vis = &self.vis;
         let struct_token = &self.struct_token;
         let ident = &self.ident;
+        let fields = &self.fields;
+        let semi_token = &self.semi_token;
+
+        // structs with native fields only are passed by value
+        let is_native = self.native_fields().is_some();
+        let repr = (is_native && self.repr().next().is_none()).then(|| quote!(#[repr(C)]));
+
+        // the derive comes first, the `serde` attributes of the user are helpers of it
+        tokens.extend(quote! {
+            #[derive(::sauro::serde::Serialize, ::sauro::serde::Deserialize)]
+            #[serde(crate = "::sauro::serde")]
+            #(#attrs)*
+            #repr
+            #vis #struct_token #ident #fields #semi_token
+        });
+
+        if is_native {
+            tokens.extend(quote! {
+                impl ::sauro::ffi::Fallback for #ident {
+                    fn fallback() -> Self {
+                        // SAFETY: all the fields are numbers, zero is a valid value
+                        unsafe { ::std::mem::zeroed() }
+                    }
+                }
+            })
+        }
+    }
+}
+
+impl quote::ToTokens for ItemEnum {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let attrs = self.attrs.iter();
+        let vis = &self.vis;
+        let enum_token = &self.enum_token;
+        let ident = &self.ident;
 
         let expanded = {
             let span = self.brace_token.span;
-            let fields = self.fields.iter();
-            quote_spanned!(span => {#(#fields),*})
+            let variants = self.variants.iter();
+            quote_spanned!(span => {#(#variants),*})
         };
 
         tokens.extend(quote! {
-            #(#attrs)*
             #[derive(::sauro::serde::Serialize, ::sauro::serde::Deserialize)]
             #[serde(crate = "::sauro::serde")]
-            #vis #struct_token #ident #expanded
+            #(#attrs)*
+            #vis #enum_token #ident #expanded
         })
     }
 }
 
+impl quote::ToTokens for Variant {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let attrs = self.attrs.iter();
+        let ident = &self.ident;
+        let fields = &self.fields;
+        let discriminant = self
+            .discriminant
+            .as_ref()
+            .map(|(eq_token, expr)| quote!(#eq_token #expr));
+
+        tokens.extend(quote! {
+            #(#attrs)*
+            #ident #fields #discriminant
+        })
+    }
+}
+
+impl quote::ToTokens for Fields {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Fields::Named(fields) => {
+                let span = fields.brace_token.span;
+                let fields = fields.named.iter();
+                tokens.extend(quote_spanned!(span => {#(#fields),*}))
+            }
+            Fields::Unnamed(fields) => {
+                let span = fields.paren_token.span;
+                let fields = fields.unnamed.iter();
+                tokens.extend(quote_spanned!(span => (#(#fields),*)))
+            }
+            Fields::Unit => {}
+        }
+    }
+}
+
 impl quote::ToTokens for ItemFn {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        let fn_inner_impl = FnInnerImpl(self);
+
+        BindingFn {
+            cfg: cfg_attrs(&self.attrs).collect(),
+            vis: &self.vis,
+            ident: self.sig.ident.clone(),
+            sig: &self.sig,
+            self_ty: None,
+            prelude: fn_inner_impl.into_token_stream(),
+            callee: quote!(__inner_impl),
+        }
+        .to_tokens(tokens)
+    }
+}
+
+impl quote::ToTokens for ItemOpaque {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let item = &self.item;
+        let ident = &item.ident;
+        let free_symbol = self.free_symbol();
+        let export_name = free_symbol.to_string();
+        let cfg = cfg_attrs(&item.attrs);
+
+        tokens.extend(quote! {
+            #item
+
+            #(#cfg)*
+            #[export_name = ::std::concat!(::std::module_path!(), "::", #export_name)]
+            #[allow(non_snake_case)]
+            pub unsafe extern "C" fn #free_symbol(__self: *mut #ident) {
+                if !__self.is_null() {
+                    ::std::mem::drop(unsafe { ::std::boxed::Box::from_raw(__self) });
+                }
+            }
+        })
+    }
+}
+
+impl quote::ToTokens for ItemImpl {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let attrs = self.attrs.iter();
+        let impl_token = &self.impl_token;
+        let self_ty = &self.self_ty;
+
+        let expanded = {
+            let span = self.brace_token.span;
+            let items = self.items.iter();
+            quote_spanned!(span => {#(#items)*})
+        };
+
+        let bindings = self.items.iter().map(|item| {
+            let ident = &item.sig.ident;
+            BindingFn {
+                cfg: cfg_attrs(&self.attrs)
+                    .chain(cfg_attrs(&item.attrs))
+                    .collect(),
+                vis: &item.vis,
+                ident: item.symbol(self_ty),
+                sig: &item.sig,
+                self_ty: Some(self_ty),
+                prelude: TokenStream::new(),
+                callee: quote!(#self_ty::#ident),
+            }
+        });
+
+        tokens.extend(quote! {
+            #(#attrs)*
+            #impl_token #self_ty #expanded
+
+            #(
+                #[allow(non_snake_case)]
+                #bindings
+            )*
+        })
+    }
+}
+
+impl quote::ToTokens for ImplItemFn {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let attrs = self.attrs.iter();
+        let vis = &self.vis;
+        let asyncness = &self.sig.asyncness;
+        let fn_token = &self.sig.fn_token;
+        let ident = &self.sig.ident;
+
+        let inputs = {
+            let span = self.sig.paren_token.span;
+            let receiver = self.sig.receiver.iter();
+            let inputs = self.sig.inputs.iter();
+            quote_spanned!(span => (#(#receiver,)* #(#inputs),*))
+        };
+
+        let output = &self.sig.output;
+        let block = &self.block;
+
+        tokens.extend(quote! {
+            #(#attrs)*
+            #vis #asyncness #fn_token #ident #inputs #output
+            #block
+        })
+    }
+}
+
+impl quote::ToTokens for Receiver {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let and_token = &self.and_token;
+        let mutability = &self.mutability;
+        let self_token = &self.self_token;
+
+        tokens.extend(quote!(#and_token #mutability #self_token))
+    }
+}
+
+impl quote::ToTokens for Field {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let attrs = self.attrs.iter();
         let vis = &self.vis;
+        let ident = &self.ident;
+        let colon_token = &self.colon_token;
+        let ty = &self.ty;
+
+        tokens.extend(quote! {
+            #(#attrs)*
+            #vis #ident #colon_token #ty
+        })
+    }
+}
+
+/// The `cfg` attributes, they are copied on the generated items
+fn cfg_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("cfg"))
+}
+
+/// The exported function, it converts the arguments and the result and calls the implementation
+struct BindingFn<'a> {
+    cfg: Vec<&'a Attribute>,
+    vis: &'a Token![pub],
+    ident: Ident,
+    sig: &'a Signature,
+    self_ty: Option<&'a Ident>,
+    prelude: TokenStream,
+    callee: TokenStream,
+}
+
+impl<'a> ToTokens for BindingFn<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let cfg = &self.cfg;
+        let vis = self.vis;
         let sig = {
             let fn_token = {
                 let span = self.sig.fn_token.span();
@@ -68,53 +372,76 @@ impl quote::ToTokens for ItemFn {
                 let fn_token = &self.sig.fn_token;
                 quote_spanned!(span => #unsafety #abi #fn_token)
             };
-            let ident = &self.sig.ident;
+            let ident = &self.ident;
 
             let inputs = {
                 let span = self.sig.paren_token.span;
+                let receiver = self.sig.receiver.as_ref().map(|_| {
+                    let self_ty = self.self_ty;
+                    quote_spanned!(span => __self: *mut #self_ty,)
+                });
                 let inputs = self.sig.inputs.iter().enumerate().map(BindingFnArg);
-                quote_spanned!(span => (#(#inputs),*))
+                quote_spanned!(span => (#receiver #(#inputs,)* __error: *mut *const u8))
             };
             let output = BindingReturnType(&self.sig.output);
 
             quote!(#fn_token #ident #inputs #output)
         };
 
-        let fn_inner_impl = FnInnerImpl(self);
+        let prelude = &self.prelude;
+
+        let receiver_override = self.sig.receiver.as_ref().map(|receiver| {
+            let span = receiver.self_token.span;
+            let mutability = &receiver.mutability;
+            quote_spanned!(span => let __self = unsafe { &#mutability *__self };)
+        });
 
         let overrides = self.sig.inputs.iter().enumerate().map(BindingFnArgOverride);
 
-        let inputs_ident = self.sig.inputs.iter().map(|arg| &arg.ident);
+        let call = {
+            let callee = &self.callee;
+            let inputs_ident = self
+                .sig
+                .receiver
+                .as_ref()
+                .map(|_| quote!(__self))
+                .into_iter()
+                .chain(
+                    self.sig
+                        .inputs
+                        .iter()
+                        .map(|arg| arg.ident.to_token_stream()),
+                );
+            // asynchronous functions are run to completion
+            match &self.sig.asyncness {
+                Some(_) => quote!(::sauro::runtime::block_on(#callee(#(#inputs_ident),*))),
+                None => quote!(#callee(#(#inputs_ident),*)),
+            }
+        };
 
         let return_stmt = BindingReturnStmt(&self.sig.output);
 
+        // the path of the module makes the symbol unique, see `syntax::export_name`
+        let export_name = self.ident.to_string();
+
         tokens.extend(quote! {
-            #[no_mangle]
+            #(#cfg)*
+            #[export_name = ::std::concat!(::std::module_path!(), "::", #export_name)]
             #vis #sig {
-                #fn_inner_impl
-                #(#overrides)*
-                let __inner_res = __inner_impl(#(#inputs_ident),*);
-                #return_stmt
+                #prelude
+                ::sauro::ffi::call(__error, move || {
+                    #receiver_override
+                    #(#overrides)*
+                    // the deprecation is reported to the callers of the bindings
+                    #[allow(deprecated)]
+                    let __inner_res = #call;
+                    ::std::result::Result::Ok(#return_stmt)
+                })
             }
         })
     }
 }
 
-impl quote::ToTokens for Field {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
-        let attrs = self.attrs.iter();
-        let vis = &self.vis;
-        let ident = &self.ident;
-        let colon_token = &self.colon_token;
-        let ty = &self.ty;
-
-        tokens.extend(quote! {
-            #(#attrs)*
-            #vis #ident #colon_token #ty
-        })
-    }
-}
-
 struct FnInnerImpl<'a>(&'a ItemFn);
 
 impl<'a> ToTokens for FnInnerImpl<'a> {
@@ -122,6 +449,7 @@ impl<'a> ToTokens for FnInnerImpl<'a> {
         let input = self.0;
 
         let attrs = &input.attrs;
+        let asyncness = &input.sig.asyncness;
         let fn_token = &input.sig.fn_token;
         let ident = {
             let span = input.sig.ident.span();
@@ -140,7 +468,7 @@ impl<'a> ToTokens for FnInnerImpl<'a> {
 
         tokens.extend(quote! {
             #(#attrs)*
-            #fn_token #ident #inputs #output
+            #asyncness #fn_token #ident #inputs #output
             #block
         })
     }
@@ -166,22 +494,97 @@ impl<'a> ToTokens for BindingFnArg<'a> {
         let span = input.span();
         let colon_token = &input.colon_token;
 
-        let expand = if let TypeKind::Native(_) = input.ty.kind {
-            let ty = &input.ty.ty;
-            let ident = format_ident!("__arg{}", index);
-            quote_spanned!(span => #ident #colon_token #ty)
-        } else {
-            let ident_ptr = format_ident!("__arg{}_ptr", index);
-            let ident_len = format_ident!("__arg{}_len", index);
-            quote_spanned! {span =>
-                #ident_ptr #colon_token *mut u8,
-                #ident_len #colon_token usize
+        let expand = match &input.ty.kind {
+            TypeKind::Native(_) | TypeKind::Struct(_) => {
+                let ty = &input.ty.ty;
+                let ident = format_ident!("__arg{}", index);
+                quote_spanned!(span => #ident #colon_token #ty)
+            }
+            TypeKind::NonZero(ty) => {
+                let ident = format_ident!("__arg{}", index);
+                quote_spanned!(span => #ident #colon_token #ty)
+            }
+            TypeKind::Callback(callback) => {
+                let ty = CallbackPointerType(callback, &input.ty);
+                let ident = format_ident!("__arg{}", index);
+                quote_spanned!(span => #ident #colon_token #ty)
+            }
+            _ => {
+                let ident_ptr = format_ident!("__arg{}_ptr", index);
+                let ident_len = format_ident!("__arg{}_len", index);
+                quote_spanned! {span =>
+                    #ident_ptr #colon_token *mut u8,
+                    #ident_len #colon_token usize
+                }
             }
         };
         tokens.extend(expand);
     }
 }
 
+/// The function pointer received by the binding, strings and JSON values are passed as pointer and
+/// length
+struct CallbackPointerType<'a>(&'a TypeCallback, &'a Type);
+
+impl<'a> ToTokens for CallbackPointerType<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let CallbackPointerType(callback, ty) = self;
+        if let CallbackMode::Pointer = callback.mode {
+            ty.to_tokens(tokens);
+            return;
+        }
+
+        let inputs = callback.inputs.iter().map(|input| match input.kind {
+            TypeKind::Native(_) => input.to_token_stream(),
+            _ => quote!(*const u8, usize),
+        });
+        let output = callback.output.as_ref().map(|output| quote!(-> #output));
+
+        tokens.extend(quote!(extern "C" fn(#(#inputs),*) #output))
+    }
+}
+
+/// The closure passed to the implementation, it calls the function pointer received by the binding
+struct CallbackClosure<'a>(&'a TypeCallback, &'a Ident);
+
+impl<'a> ToTokens for CallbackClosure<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let CallbackClosure(callback, pointer) = self;
+
+        let args = (0..callback.inputs.len())
+            .map(|index| format_ident!("__cb{}", index))
+            .collect::<Vec<_>>();
+        let inputs = callback.inputs.iter();
+        let output = callback.output.as_ref().map(|output| quote!(-> #output));
+
+        let encode = callback.inputs.iter().zip(&args).map(|(input, arg)| {
+            if let TypeKind::Json = input.kind {
+                quote! {
+                    let #arg = ::sauro::serde_json::to_vec(&#arg)
+                        .expect("failed to serialize callback argument");
+                }
+            } else {
+                TokenStream::new()
+            }
+        });
+        let call_args = callback
+            .inputs
+            .iter()
+            .zip(&args)
+            .map(|(input, arg)| match input.kind {
+                TypeKind::Native(_) => quote!(#arg),
+                _ => quote!(#arg.as_ptr(), #arg.len()),
+            });
+
+        tokens.extend(quote! {
+            move |#(#args: #inputs),*| #output {
+                #(#encode)*
+                #pointer(#(#call_args),*)
+            }
+        })
+    }
+}
+
 struct BindingFnArgOverride<'a>((usize, &'a FnArg));
 
 impl<'a> ToTokens for BindingFnArgOverride<'a> {
@@ -197,8 +600,64 @@ impl<'a> ToTokens for BindingFnArgOverride<'a> {
 
         let ty = &input.ty.ty;
 
-        let expand = match input.ty.kind {
-            TypeKind::Native(_) => quote_spanned!(span => let #ident = #ident_arg;),
+        let expand = match &input.ty.kind {
+            TypeKind::Native(_) | TypeKind::Struct(_) => {
+                quote_spanned!(span => let #ident = #ident_arg;)
+            }
+            TypeKind::NonZero(_) => {
+                quote_spanned! {span =>
+                    let #ident = <#ty>::new(#ident_arg)
+                        .ok_or_else(|| ::sauro::ffi::Error::decode("unexpected zero value"))?;
+                }
+            }
+            TypeKind::I128 | TypeKind::U128 => {
+                quote_spanned! {span =>
+                    let #ident: #ty = {
+                        let buf = unsafe {
+                            ::std::slice::from_raw_parts(#ident_ptr, #ident_len)
+                        };
+                        <[u8; 16]>::try_from(buf)
+                            .map(<#ty>::from_le_bytes)
+                            .map_err(::sauro::ffi::Error::decode)?
+                    };
+                }
+            }
+            TypeKind::ArrayBorrowed(elem, _) => {
+                quote_spanned! {span =>
+                    let #ident: #ty = unsafe {
+                        ::std::slice::from_raw_parts(
+                            #ident_ptr as *const #elem,
+                            #ident_len / ::std::mem::size_of::<#elem>(),
+                        )
+                    }
+                    .try_into()
+                    .map_err(::sauro::ffi::Error::decode)?;
+                }
+            }
+            TypeKind::ArrayBorrowedMut(elem, _) => {
+                quote_spanned! {span =>
+                    let #ident: #ty = unsafe {
+                        ::std::slice::from_raw_parts_mut(
+                            #ident_ptr as *mut #elem,
+                            #ident_len / ::std::mem::size_of::<#elem>(),
+                        )
+                    }
+                    .try_into()
+                    .map_err(::sauro::ffi::Error::decode)?;
+                }
+            }
+            TypeKind::ArrayOwned(elem, _) => {
+                quote_spanned! {span =>
+                    let #ident: #ty = unsafe {
+                        ::std::slice::from_raw_parts(
+                            #ident_ptr as *const #elem,
+                            #ident_len / ::std::mem::size_of::<#elem>(),
+                        )
+                    }
+                    .try_into()
+                    .map_err(::sauro::ffi::Error::decode)?;
+                }
+            }
             TypeKind::BufferBorrowed(elem) => {
                 quote_spanned! {span =>
                     let #ident: #ty = unsafe {
@@ -239,17 +698,38 @@ impl<'a> ToTokens for BindingFnArgOverride<'a> {
                         let buf = unsafe {
                             ::std::slice::from_raw_parts(#ident_ptr, #ident_len)
                         };
-                        sauro::serde_json::from_slice(buf).expect("failed to deserialize binding arguments")
+                        ::sauro::serde_json::from_slice(buf).map_err(::sauro::ffi::Error::decode)?
                     };
                 }
             }
+            // rejected by the parser, the handles are only returned by their methods
+            TypeKind::Opaque => {
+                syn::Error::new_spanned(ty, "opaque handles are not supported as arguments")
+                    .into_compile_error()
+            }
+            TypeKind::Callback(callback) => {
+                let closure_ident = format_ident!("__arg{}_closure", index);
+                let closure = CallbackClosure(callback, &ident_arg);
+                match callback.mode {
+                    CallbackMode::Impl => quote_spanned!(span => let #ident = #closure;),
+                    CallbackMode::Dyn => quote_spanned! {span =>
+                        let #closure_ident = #closure;
+                        let #ident = &#closure_ident;
+                    },
+                    CallbackMode::DynMut => quote_spanned! {span =>
+                        let mut #closure_ident = #closure;
+                        let #ident = &mut #closure_ident;
+                    },
+                    CallbackMode::Pointer => quote_spanned!(span => let #ident = #ident_arg;),
+                }
+            }
             TypeKind::StringBorrowed => {
                 quote_spanned! {span =>
                     let #ident = {
                         let buf = unsafe {
                             ::std::slice::from_raw_parts(#ident_ptr, #ident_len)
                         };
-                        ::std::str::from_utf8(buf).expect("failed to deserialize string")
+                        ::std::str::from_utf8(buf).map_err(::sauro::ffi::Error::decode)?
                     };
                 }
             }
@@ -260,7 +740,7 @@ impl<'a> ToTokens for BindingFnArgOverride<'a> {
                             ::std::slice::from_raw_parts(#ident_ptr, #ident_len)
                         };
                         let buf = buf.to_vec();
-                        ::std::string::String::from_utf8(buf).expect("failed to deserialize string")
+                        ::std::string::String::from_utf8(buf).map_err(::sauro::ffi::Error::decode)?
                     };
                 }
             }
@@ -290,10 +770,11 @@ impl<'a> ToTokens for BindingReturnType<'a> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let input = self.0;
         if let ReturnType::Type(rarrow, ty) = input {
-            let expand = if let TypeKind::Native(_) = ty.kind {
-                quote!(#rarrow #ty)
-            } else {
-                quote!(#rarrow *const u8 )
+            let expand = match ty.kind {
+                TypeKind::Native(_) | TypeKind::Struct(_) => quote!(#rarrow #ty),
+                TypeKind::NonZero(elem) => quote!(#rarrow #elem),
+                TypeKind::Opaque => quote!(#rarrow *mut #ty),
+                _ => quote!(#rarrow *const u8 ),
             };
             tokens.extend(expand);
         }
@@ -307,59 +788,70 @@ impl<'a> ToTokens for BindingReturnStmt<'a> {
         let input = self.0;
 
         let ReturnType::Type(_, ty) = input else {
+            tokens.extend(quote!(__inner_res));
             return;
         };
 
         let expand = match ty.kind {
-            TypeKind::Native(_) => quote!(__inner_res),
-            TypeKind::BufferBorrowed(elem)
+            TypeKind::Native(_) | TypeKind::Struct(_) => quote!(__inner_res),
+            TypeKind::NonZero(_) => quote!(__inner_res.get()),
+            TypeKind::I128 | TypeKind::U128 => {
+                quote!(::sauro::ffi::len_prefixed(&__inner_res.to_le_bytes()))
+            }
+            // rejected by the parser, the callbacks are released when the call returns
+            TypeKind::Callback(_) => {
+                syn::Error::new_spanned(&ty.ty, "callbacks are supported only as arguments")
+                    .into_compile_error()
+            }
+            TypeKind::Opaque => {
+                quote!(::std::boxed::Box::into_raw(::std::boxed::Box::new(
+                    __inner_res
+                )))
+            }
+            TypeKind::ArrayBorrowed(elem, _)
+            | TypeKind::ArrayBorrowedMut(elem, _)
+            | TypeKind::ArrayOwned(elem, _)
+            | TypeKind::BufferBorrowed(elem)
             | TypeKind::BufferBorrowedMut(elem)
             | TypeKind::BufferOwned(elem) => {
                 quote! {{
                     let x: #ty = __inner_res;
-                    let encoded_length = ((x.len() * std::mem::size_of::<#elem>()) as u32).to_be_bytes();
-
-                    let mut buffer = encoded_length.to_vec();
-                    buffer.extend_from_slice(unsafe {
+                    let encoded = unsafe {
                         ::std::slice::from_raw_parts(
                             x.as_ptr() as *const u8,
                             x.len() * ::std::mem::size_of::<#elem>(),
                         )
-                    });
+                    };
+                    ::sauro::ffi::len_prefixed(encoded)
+                }}
+            }
+            TypeKind::Json if ty.is_result() => {
+                quote! {{
+                    use ::sauro::ffi::{DisplayErrorMessage as _, NoErrorMessage as _};
 
-                    let ptr = buffer.as_ptr();
-                    ::std::mem::forget(buffer);
-                    ptr
+                    let x: #ty = __inner_res;
+                    let json = match &x {
+                        ::std::result::Result::Ok(_) => ::sauro::serde_json::to_string(&x),
+                        ::std::result::Result::Err(err) => {
+                            let message = (&::sauro::ffi::ErrorMessage(err)).message();
+                            ::sauro::ffi::encode_result_err(err, message)
+                        }
+                    }
+                    .expect("failed to serialize binding result");
+                    ::sauro::ffi::len_prefixed(json.as_bytes())
                 }}
             }
             TypeKind::Json => {
                 quote! {{
                     let x: #ty = __inner_res;
-                    let json = sauro::serde_json::to_string(&x).expect("failed to serialize binding result");
-
-                    let encoded_json = json.into_bytes();
-                    let encoded_length = (encoded_json.len() as u32).to_be_bytes();
-
-                    let mut buffer = encoded_length.to_vec();
-                    buffer.extend(encoded_json);
-
-                    let ptr = buffer.as_ptr();
-                    ::std::mem::forget(buffer);
-                    ptr
+                    let json = ::sauro::serde_json::to_string(&x).expect("failed to serialize binding result");
+                    ::sauro::ffi::len_prefixed(json.as_bytes())
                 }}
             }
             TypeKind::StringBorrowed | TypeKind::StringOwned => {
                 quote! {{
                     let x: #ty = __inner_res;
-                    let encoded_str = x.as_bytes();
-                    let encoded_length = (encoded_str.len() as u32).to_be_bytes();
-
-                    let mut buffer = encoded_length.to_vec();
-                    buffer.extend(encoded_str);
-
-                    let ptr = buffer.as_ptr();
-                    ::std::mem::forget(buffer);
-                    ptr
+                    ::sauro::ffi::len_prefixed(x.as_bytes())
                 }}
             }
         };
@@ -371,6 +863,7 @@ impl<'a> ToTokens for BindingReturnStmt<'a> {
 impl ToTokens for TypeNative {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         tokens.extend(match self {
+            Self::Bool => quote!(bool),
             Self::I8 => quote!(i8),
             Self::I16 => quote!(i16),
             Self::I32 => quote!(i32),
@@ -1,3 +1,4 @@
+pub mod abi;
 pub mod expand;
 pub mod syntax;
 pub mod typescript;
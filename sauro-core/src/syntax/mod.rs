@@ -1,10 +1,14 @@
 mod parse;
+mod serde;
 
-use syn::{punctuated::Punctuated, token, Attribute, Block, Ident, Token};
+use syn::{ext::IdentExt, punctuated::Punctuated, token, Attribute, Block, Expr, Ident, Token};
 
 use crate::typescript;
 
-pub use self::parse::parse_module;
+pub use self::{
+    parse::parse_module,
+    serde::{RenameRule, Serde},
+};
 
 pub struct Module {
     pub attrs: Vec<Attribute>,
@@ -13,28 +17,151 @@ pub struct Module {
     pub ident: Ident,
     pub brace_token: token::Brace,
     pub items: Vec<Item>,
+    pub metadata: Vec<Metadata>,
+}
+
+/// The description of an item embedded in the compiled library, function bodies are stripped
+pub struct Metadata {
+    pub cfg: Vec<Attribute>,
+    pub item: syn::Item,
 }
 
 pub enum Item {
+    Enum(ItemEnum),
     Fn(ItemFn),
+    Impl(ItemImpl),
+    Opaque(ItemOpaque),
     Struct(ItemStruct),
 }
 
+pub struct ItemEnum {
+    pub attrs: Vec<Attribute>,
+    pub vis: Token![pub],
+    pub enum_token: Token![enum],
+    pub ident: Ident,
+    pub brace_token: token::Brace,
+    pub variants: Punctuated<Variant, Token![,]>,
+    pub serde: Serde,
+}
+
+impl ItemEnum {
+    /// Check if all the variants have no fields
+    pub fn is_unit_only(&self) -> bool {
+        self.variants
+            .iter()
+            .all(|variant| matches!(variant.fields, Fields::Unit))
+    }
+}
+
+pub struct Variant {
+    pub attrs: Vec<Attribute>,
+    pub ident: Ident,
+    pub serde: Serde,
+    pub fields: Fields,
+    pub discriminant: Option<(Token![=], Expr)>,
+}
+
+impl Variant {
+    /// The name of the variant in the JSON representation
+    pub fn serde_name(&self, rename_all: Option<RenameRule>) -> String {
+        let ident = self.ident.unraw().to_string();
+        match (&self.serde.rename, rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_variant(&ident),
+            (None, None) => ident,
+        }
+    }
+}
+
 pub struct ItemStruct {
     pub attrs: Vec<Attribute>,
     pub vis: Token![pub],
     pub struct_token: Token![struct],
     pub ident: Ident,
+    pub fields: Fields,
+    pub semi_token: Option<Token![;]>,
+    pub serde: Serde,
+}
+
+impl ItemStruct {
+    /// The types of the fields when the struct is passed by value, it requires native fields only
+    /// and a C compatible representation.
+    pub fn native_fields(&self) -> Option<Vec<TypeNative>> {
+        let is_repr_c = self
+            .repr()
+            .all(|attr| attr.parse_args::<Ident>().is_ok_and(|repr| repr == "C"));
+        if !is_repr_c || self.fields.iter().next().is_none() {
+            return None;
+        }
+        // the fields must be encoded as they are declared
+        let is_plain = |serde: &Serde| !serde.skip && !serde.optional && !serde.flatten;
+        if !is_plain(&self.serde) || !self.fields.iter().all(|field| is_plain(&field.serde)) {
+            return None;
+        }
+        self.fields
+            .iter()
+            .map(|field| match field.ty.kind {
+                TypeKind::Native(ty) => Some(ty),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The `repr` attributes of the struct
+    pub fn repr(&self) -> impl Iterator<Item = &Attribute> {
+        self.attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("repr"))
+    }
+}
+
+pub enum Fields {
+    Named(FieldsNamed),
+    Unnamed(FieldsUnnamed),
+    Unit,
+}
+
+impl Fields {
+    pub fn iter(&self) -> impl Iterator<Item = &Field> {
+        let fields = match self {
+            Fields::Named(fields) => Some(fields.named.iter()),
+            Fields::Unnamed(fields) => Some(fields.unnamed.iter()),
+            Fields::Unit => None,
+        };
+        fields.into_iter().flatten()
+    }
+}
+
+pub struct FieldsNamed {
     pub brace_token: token::Brace,
-    pub fields: Punctuated<Field, Token![,]>,
+    pub named: Punctuated<Field, Token![,]>,
+}
+
+pub struct FieldsUnnamed {
+    pub paren_token: token::Paren,
+    pub unnamed: Punctuated<Field, Token![,]>,
 }
 
 pub struct Field {
     pub attrs: Vec<Attribute>,
-    pub vis: Token![pub],
-    pub ident: Ident,
-    pub colon_token: Token![:],
+    pub vis: Option<Token![pub]>,
+    pub ident: Option<Ident>,
+    pub colon_token: Option<Token![:]>,
     pub ty: Type,
+    pub serde: Serde,
+}
+
+impl Field {
+    /// The name of the property in the JSON representation, `None` for tuple fields
+    pub fn serde_name(&self, rename_all: Option<RenameRule>) -> Option<String> {
+        let ident = self.ident.as_ref()?.unraw().to_string();
+        let name = match (&self.serde.rename, rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_field(&ident),
+            (None, None) => ident,
+        };
+        Some(name)
+    }
 }
 
 pub struct ItemFn {
@@ -44,14 +171,63 @@ pub struct ItemFn {
     pub block: Box<Block>,
 }
 
+/// A struct exposed as an opaque handle, its fields are never exposed.
+pub struct ItemOpaque {
+    pub item: syn::ItemStruct,
+}
+
+impl ItemOpaque {
+    /// Name of the function used to release the handle, see [`export_name`]
+    pub fn free_symbol(&self) -> Ident {
+        quote::format_ident!("{}_free", self.item.ident)
+    }
+}
+
+pub struct ItemImpl {
+    pub attrs: Vec<Attribute>,
+    pub impl_token: Token![impl],
+    pub self_ty: Ident,
+    pub brace_token: token::Brace,
+    pub items: Vec<ImplItemFn>,
+}
+
+pub struct ImplItemFn {
+    pub attrs: Vec<Attribute>,
+    pub vis: Token![pub],
+    pub sig: Signature,
+    pub block: Block,
+}
+
+impl ImplItemFn {
+    /// Name of the function binding the method, see [`export_name`]
+    pub fn symbol(&self, self_ty: &Ident) -> Ident {
+        quote::format_ident!("{}_{}", self_ty, self.sig.ident)
+    }
+}
+
+/// Name of the symbol exported by the binding of a function, the path of the module makes it
+/// unique in the library (e.g. `my_crate::api::Counter_new`)
+pub fn export_name(module_path: &str, symbol: &Ident) -> String {
+    format!("{}::{}", module_path, symbol)
+}
+
 pub struct Signature {
+    pub asyncness: Option<Token![async]>,
     pub fn_token: Token![fn],
     pub ident: Ident,
     pub paren_token: token::Paren,
+    pub receiver: Option<Receiver>,
     pub inputs: Punctuated<FnArg, Token![,]>,
     pub output: ReturnType,
 }
 
+/// The `&self` or `&mut self` argument of a method
+pub struct Receiver {
+    pub and_token: Token![&],
+    pub mutability: Option<Token![mut]>,
+    pub self_token: Token![self],
+}
+
 pub struct FnArg {
     pub mutability: Option<Token![mut]>,
     pub ident: Ident,
@@ -68,21 +244,81 @@ pub struct Type {
     pub ty: Box<syn::Type>,
     pub kind: TypeKind,
     pub ts: typescript::Type,
-    pub is_result: bool,
+    /// The error type, if it is a `Result`
+    pub error: Option<Box<Type>>,
+}
+
+impl Type {
+    pub fn is_result(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// `()`, encoded as `null`
+    pub fn is_unit(&self) -> bool {
+        matches!(self.ty.as_ref(), syn::Type::Tuple(ty) if ty.elems.is_empty())
+    }
+
+    /// `char`, encoded as a string containing a single character
+    pub fn is_char(&self) -> bool {
+        self.ident().is_some_and(|ident| ident == "char")
+    }
+
+    /// The name of the type, if it is a plain identifier (e.g. a type defined in the module)
+    pub fn ident(&self) -> Option<&Ident> {
+        match self.ty.as_ref() {
+            syn::Type::Path(ty) if ty.qself.is_none() => ty.path.get_ident(),
+            _ => None,
+        }
+    }
 }
 
 pub enum TypeKind {
+    /// `&[T; N]`, passed as a buffer whose length is checked
+    ArrayBorrowed(TypeNative, usize),
+    /// `&mut [T; N]`, passed as a buffer whose length is checked
+    ArrayBorrowedMut(TypeNative, usize),
+    /// `[T; N]`, passed as a buffer whose length is checked
+    ArrayOwned(TypeNative, usize),
     BufferBorrowed(TypeNative),
     BufferBorrowedMut(TypeNative),
     BufferOwned(TypeNative),
+    /// `i128`, passed as a little endian buffer of 16 bytes
+    I128,
     Json,
     Native(TypeNative),
+    /// A non-zero integer, passed as the integer and checked by both sides
+    NonZero(TypeNative),
+    Opaque,
+    /// A function called by the implementation during the call
+    Callback(Box<TypeCallback>),
+    /// A struct with native fields only, passed by value
+    Struct(Vec<TypeNative>),
     StringBorrowed,
     StringOwned,
+    /// `u128`, passed as a little endian buffer of 16 bytes
+    U128,
+}
+
+pub struct TypeCallback {
+    pub mode: CallbackMode,
+    pub inputs: Vec<Type>,
+    pub output: Option<Type>,
+}
+
+pub enum CallbackMode {
+    /// `impl Fn(..)`, `impl FnMut(..)` or `impl FnOnce(..)`
+    Impl,
+    /// `&dyn Fn(..)`
+    Dyn,
+    /// `&mut dyn FnMut(..)`
+    DynMut,
+    /// `extern "C" fn(..)`, the function pointer is passed as is
+    Pointer,
 }
 
 #[derive(Clone, Copy)]
 pub enum TypeNative {
+    Bool,
     I8,
     I16,
     I32,
@@ -100,6 +336,7 @@ pub enum TypeNative {
 impl TypeNative {
     pub fn symbol(&self) -> &'static str {
         match self {
+            TypeNative::Bool => "bool",
             TypeNative::I8 => "i8",
             TypeNative::I16 => "i16",
             TypeNative::I32 => "i32",
@@ -114,4 +351,49 @@ impl TypeNative {
             TypeNative::F64 => "f64",
         }
     }
+
+    /// The size in bytes, it is also the alignment. The pointers are 64-bit on all the platforms
+    /// supported by Deno, the CLI rejects the other targets.
+    pub fn size(&self) -> usize {
+        match self {
+            TypeNative::Bool | TypeNative::I8 | TypeNative::U8 => 1,
+            TypeNative::I16 | TypeNative::U16 => 2,
+            TypeNative::I32 | TypeNative::U32 | TypeNative::F32 => 4,
+            TypeNative::I64
+            | TypeNative::ISize
+            | TypeNative::U64
+            | TypeNative::USize
+            | TypeNative::F64 => 8,
+        }
+    }
+}
+
+/// The `#[repr(C)]` layout of a struct with native fields only
+#[derive(Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// The offset of each field, in declaration order
+    pub offsets: Vec<usize>,
+    pub size: usize,
+    pub align: usize,
+}
+
+impl Layout {
+    pub fn new(fields: &[TypeNative]) -> Self {
+        // every field is aligned to its size, and the struct to the largest field
+        let mut offsets = Vec::with_capacity(fields.len());
+        let mut size = 0usize;
+        for ty in fields {
+            let offset = size.next_multiple_of(ty.size());
+            offsets.push(offset);
+            size = offset + ty.size();
+        }
+        let align = fields.iter().map(|ty| ty.size()).max().unwrap_or(1);
+        let size = size.next_multiple_of(align);
+
+        Layout {
+            offsets,
+            size,
+            align,
+        }
+    }
 }
@@ -1,22 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
 use proc_macro2::Span;
-use syn::{punctuated::Punctuated, spanned::Spanned, Pat, Token, Visibility};
+use quote::ToTokens;
+use syn::{
+    parse_quote,
+    punctuated::{Pair, Punctuated},
+    spanned::Spanned,
+    Ident, Pat, Token, Visibility,
+};
 
 use crate::typescript;
 
 use super::{
-    Field, FnArg, Item, ItemFn, ItemStruct, Module, ReturnType, Signature, Type, TypeKind,
-    TypeNative,
+    CallbackMode, Field, Fields, FieldsNamed, FieldsUnnamed, FnArg, ImplItemFn, Item, ItemEnum,
+    ItemFn, ItemImpl, ItemOpaque, ItemStruct, Metadata, Module, Receiver, ReturnType, Serde,
+    Signature, Type, TypeCallback, TypeKind, TypeNative, Variant,
 };
 
 pub fn parse_module(input: syn::ItemMod) -> syn::Result<Module> {
     let Some((brace_token, items)) = input.content else {
         return Err(syn::Error::new_spanned(&input, "modules can not be empty"));
     };
+    let items = items
+        .into_iter()
+        .flat_map(split_conditional_methods)
+        .collect::<Vec<_>>();
+
+    let metadata = items.iter().map(Metadata::from).collect();
+
+    let structs = items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Struct(item) => Some(item.ident.clone()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    // structs with an impl block are exposed as opaque handles
+    let opaque_types = items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Impl(item) => impl_self_ty(item),
+            _ => None,
+        })
+        .cloned()
+        .collect::<HashSet<_>>();
 
+    // all the items are parsed, so that all the errors are reported at once
+    let mut errors = Errors::default();
     let items = items
         .into_iter()
-        .map(Item::try_from)
-        .collect::<syn::Result<_>>()?;
+        .map(|item| match item {
+            syn::Item::Struct(item) if opaque_types.contains(&item.ident) => {
+                ItemOpaque::try_from(item).map(Item::Opaque)
+            }
+            item => Item::try_from(item),
+        })
+        .filter_map(|item| errors.check(item))
+        .collect::<Vec<_>>();
+
+    for item in &items {
+        if let Item::Impl(item) = item {
+            if !structs.contains(&item.self_ty) {
+                errors.push(syn::Error::new_spanned(
+                    &item.self_ty,
+                    "impl blocks are supported only for structs defined in the same module",
+                ));
+            }
+        }
+    }
+    let unit_enums = items
+        .iter()
+        .filter_map(|item| match item {
+            // the variants of tagged enums are encoded as objects
+            Item::Enum(item) if item.serde.tag.is_none() => {
+                item.is_unit_only().then(|| item.ident.clone())
+            }
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    for ty in items.iter().flat_map(item_types) {
+        check_map_keys(&ty.ty, &unit_enums, &mut errors);
+    }
+    // the errors of the results are thrown as classes, they are not exposed as data types
+    let error_types = items
+        .iter()
+        .flat_map(item_signatures)
+        .filter_map(|sig| match &sig.output {
+            ReturnType::Type(_, ty) => ty.error.as_ref()?.ident(),
+            ReturnType::Default => None,
+        })
+        .filter(|ident| {
+            items.iter().any(|item| match item {
+                Item::Enum(item) => item.ident == **ident,
+                Item::Struct(item) => item.ident == **ident,
+                _ => false,
+            })
+        })
+        .cloned()
+        .collect::<HashSet<_>>();
+    for item in &items {
+        let self_ty = match item {
+            Item::Impl(item) => Some(&item.self_ty),
+            _ => None,
+        };
+        for path in value_types(item).into_iter().flat_map(named_types) {
+            let ident = match path.get_ident() {
+                Some(ident) if ident == "Self" => self_ty,
+                ident => ident,
+            };
+            let Some(ident) = ident else {
+                continue;
+            };
+            if error_types.contains(ident) {
+                errors.push(syn::Error::new_spanned(
+                    path,
+                    format!(
+                        "`{}` is thrown as an error class, it can not be used as a value",
+                        ident
+                    ),
+                ));
+            }
+            // the handles are not serialized, e.g. in `Result<Self, E>` or `Option<Self>`
+            if opaque_types.contains(ident) {
+                errors.push(syn::Error::new_spanned(
+                    path,
+                    format!(
+                        "`{}` is an opaque handle, it can only be returned as `Self` by its methods",
+                        ident
+                    ),
+                ));
+            }
+        }
+    }
+    let mut items = errors.finish(Ok(items))?;
+
+    // structs with native fields only are passed by value
+    let native_structs = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(item) => Some((item.ident.clone(), item.native_fields()?)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    for item in &mut items {
+        match item {
+            Item::Fn(item) => resolve_native_structs(&mut item.sig, &native_structs),
+            Item::Impl(item) => {
+                for method in &mut item.items {
+                    resolve_native_structs(&mut method.sig, &native_structs);
+                }
+            }
+            _ => {}
+        }
+    }
 
     let attrs = input.attrs;
     let vis = visibility_pub(&input.vis, input.ident.span());
@@ -30,16 +167,101 @@ pub fn parse_module(input: syn::ItemMod) -> syn::Result<Module> {
         ident,
         brace_token,
         items,
+        metadata,
     })
 }
 
+/// The methods with `cfg` attributes are moved to dedicated impl blocks, so that the metadata
+/// stored in the library describe only the methods enabled by the selected features.
+fn split_conditional_methods(item: syn::Item) -> Vec<syn::Item> {
+    let syn::Item::Impl(mut item) = item else {
+        return vec![item];
+    };
+
+    let is_conditional = |impl_item: &syn::ImplItem| match impl_item {
+        syn::ImplItem::Fn(method) => method.attrs.iter().any(|attr| attr.path().is_ident("cfg")),
+        _ => false,
+    };
+    let (conditional, unconditional) = std::mem::take(&mut item.items)
+        .into_iter()
+        .partition::<Vec<_>, _>(is_conditional);
+    item.items = unconditional;
+
+    let mut items = vec![];
+    for mut impl_item in conditional {
+        let mut conditional_item = item.clone();
+        if let syn::ImplItem::Fn(method) = &mut impl_item {
+            let (cfg, attrs) = std::mem::take(&mut method.attrs)
+                .into_iter()
+                .partition::<Vec<_>, _>(|attr| attr.path().is_ident("cfg"));
+            method.attrs = attrs;
+            conditional_item.attrs.extend(cfg);
+        }
+        conditional_item.items = vec![impl_item];
+        items.push(syn::Item::Impl(conditional_item));
+    }
+    items.insert(0, syn::Item::Impl(item));
+    items
+}
+
+fn resolve_native_structs(sig: &mut Signature, native_structs: &HashMap<Ident, Vec<TypeNative>>) {
+    let inputs = sig.inputs.iter_mut().map(|input| &mut input.ty);
+    let output = match &mut sig.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some(ty),
+    };
+    for ty in inputs.chain(output) {
+        if let TypeKind::Json = ty.kind {
+            if let Some(fields) = ty.ident().and_then(|ident| native_structs.get(ident)) {
+                ty.kind = TypeKind::Struct(fields.clone());
+            }
+        }
+    }
+}
+
+impl From<&syn::Item> for Metadata {
+    fn from(value: &syn::Item) -> Self {
+        fn strip_block(block: &mut syn::Block) {
+            block.stmts.clear();
+        }
+
+        let mut item = value.clone();
+        let attrs = match &mut item {
+            syn::Item::Fn(item) => {
+                strip_block(&mut item.block);
+                &item.attrs
+            }
+            syn::Item::Impl(item) => {
+                for impl_item in &mut item.items {
+                    if let syn::ImplItem::Fn(impl_item) = impl_item {
+                        strip_block(&mut impl_item.block);
+                    }
+                }
+                &item.attrs
+            }
+            syn::Item::Enum(item) => &item.attrs,
+            syn::Item::Struct(item) => &item.attrs,
+            _ => &[][..],
+        };
+        let cfg = attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .cloned()
+            .collect();
+
+        Metadata { cfg, item }
+    }
+}
+
 impl TryFrom<syn::Item> for Item {
     type Error = syn::Error;
 
     fn try_from(value: syn::Item) -> syn::Result<Self> {
         match value {
+            syn::Item::Enum(value) => ItemEnum::try_from(value).map(Item::Enum),
             syn::Item::Struct(value) => ItemStruct::try_from(value).map(Item::Struct),
             syn::Item::Fn(value) => ItemFn::try_from(value).map(Item::Fn),
+            syn::Item::Impl(value) => ItemImpl::try_from(value).map(Item::Impl),
             input => Err(syn::Error::new_spanned(input, "unsupported item")),
         }
     }
@@ -49,67 +271,278 @@ impl TryFrom<syn::ItemStruct> for ItemStruct {
     type Error = syn::Error;
 
     fn try_from(value: syn::ItemStruct) -> syn::Result<Self> {
+        let mut errors = Errors::default();
         let params = &value.generics.params;
         if !params.is_empty() {
-            return Err(syn::Error::new_spanned(
+            errors.push(syn::Error::new_spanned(
                 params,
                 "type parameters are not supported",
             ));
         }
 
-        let mut fields = Punctuated::new();
-        let brace_token = match value.fields {
-            syn::Fields::Named(named_fields) => {
-                for pair in named_fields.named.into_pairs() {
-                    let (field, punct) = pair.into_tuple();
-
-                    let field = field.try_into()?;
-                    fields.push_value(field);
-
-                    if let Some(punct) = punct {
-                        fields.push_punct(punct);
-                    }
-                }
-
-                named_fields.brace_token
-            }
-            syn::Fields::Unnamed(_) => {
-                return Err(syn::Error::new_spanned(
-                    value,
-                    "tuple structs are not supported",
-                ))
-            }
-            syn::Fields::Unit => {
-                return Err(syn::Error::new_spanned(
-                    value,
-                    "unit structs are not supported",
-                ))
-            }
-        };
-
+        errors.check(check_unconditional_serde(&value.attrs));
+        let serde = Serde::parse(&value.attrs);
         let attrs = value.attrs;
         let vis = visibility_pub(&value.vis, value.ident.span());
         let struct_token = value.struct_token;
         let ident = value.ident.clone();
+        let fields = parse_fields(value.fields, |field| {
+            let span = match &field.ident {
+                Some(ident) => ident.span(),
+                None => field.ty.span(),
+            };
+            Some(visibility_pub(&field.vis, span))
+        });
+        let (serde, fields) = errors.finish(join(serde, fields))?;
+        if serde.tag.is_some() || serde.content.is_some() {
+            return Err(syn::Error::new_spanned(
+                &ident,
+                "`tag` and `content` are supported only by enums",
+            ));
+        }
+        let semi_token = value.semi_token;
 
         Ok(ItemStruct {
             attrs,
             vis,
             struct_token,
             ident,
+            fields,
+            semi_token,
+            serde,
+        })
+    }
+}
+
+impl TryFrom<syn::ItemEnum> for ItemEnum {
+    type Error = syn::Error;
+
+    fn try_from(value: syn::ItemEnum) -> syn::Result<Self> {
+        let mut errors = Errors::default();
+        let params = &value.generics.params;
+        if !params.is_empty() {
+            errors.push(syn::Error::new_spanned(
+                params,
+                "type parameters are not supported",
+            ));
+        }
+
+        let variants = collect_all(value.variants.into_pairs().map(|pair| {
+            let (variant, punct) = pair.into_tuple();
+            Variant::try_from(variant).map(|variant| Pair::new(variant, punct))
+        }));
+        errors.check(check_unconditional_serde(&value.attrs));
+        let serde = Serde::parse(&value.attrs);
+        let (variants, serde) = errors.finish(join(variants, serde))?;
+        check_tagged_variants(&value.ident, &variants, &serde)?;
+        let attrs = value.attrs;
+        let vis = visibility_pub(&value.vis, value.ident.span());
+        let enum_token = value.enum_token;
+        let ident = value.ident;
+        let brace_token = value.brace_token;
+
+        Ok(ItemEnum {
+            attrs,
+            vis,
+            enum_token,
+            ident,
             brace_token,
+            variants,
+            serde,
+        })
+    }
+}
+
+/// The tagged representations supported by serde, the name of the variant is stored in the `tag`
+/// property, next to the fields (internally tagged) or next to the `content` property (adjacently
+/// tagged)
+fn check_tagged_variants(
+    ident: &Ident,
+    variants: &Punctuated<Variant, Token![,]>,
+    serde: &Serde,
+) -> syn::Result<()> {
+    match (&serde.tag, &serde.content) {
+        (None, None) | (Some(_), Some(_)) => Ok(()),
+        (None, Some(_)) => Err(syn::Error::new_spanned(ident, "`content` requires `tag`")),
+        (Some(_), None) => {
+            let mut errors = Errors::default();
+            for variant in variants {
+                if let Fields::Unnamed(_) = variant.fields {
+                    errors.push(syn::Error::new_spanned(
+                        &variant.ident,
+                        "internally tagged enums can not have tuple variants, add `content` to store the fields apart",
+                    ));
+                }
+            }
+            errors.finish(Ok(()))
+        }
+    }
+}
+
+impl TryFrom<syn::Variant> for Variant {
+    type Error = syn::Error;
+
+    fn try_from(value: syn::Variant) -> syn::Result<Self> {
+        let unconditional = check_unconditional(&value.attrs);
+        let serde = Serde::parse(&value.attrs);
+        let attrs = value.attrs;
+        let ident = value.ident;
+        // fields of enum variants inherit the visibility of the enum
+        let fields = parse_fields(value.fields, |_| None);
+        let ((_, serde), fields) = join(join(unconditional, serde), fields)?;
+        let discriminant = value.discriminant;
+
+        Ok(Variant {
+            attrs,
+            ident,
+            serde,
             fields,
+            discriminant,
+        })
+    }
+}
+
+impl TryFrom<syn::ItemStruct> for ItemOpaque {
+    type Error = syn::Error;
+
+    fn try_from(mut value: syn::ItemStruct) -> syn::Result<Self> {
+        let params = &value.generics.params;
+        if !params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                params,
+                "type parameters are not supported",
+            ));
+        }
+
+        let vis = visibility_pub(&value.vis, value.ident.span());
+        value.vis = Visibility::Public(vis);
+
+        Ok(ItemOpaque { item: value })
+    }
+}
+
+impl TryFrom<syn::ItemImpl> for ItemImpl {
+    type Error = syn::Error;
+
+    fn try_from(value: syn::ItemImpl) -> syn::Result<Self> {
+        let mut errors = Errors::default();
+        let params = &value.generics.params;
+        if !params.is_empty() {
+            errors.push(syn::Error::new_spanned(
+                params,
+                "type parameters are not supported",
+            ));
+        }
+        if let Some((_, path, _)) = &value.trait_ {
+            errors.push(syn::Error::new_spanned(
+                path,
+                "trait implementations are not supported",
+            ));
+        }
+        if value.unsafety.is_some() {
+            errors.push(syn::Error::new_spanned(
+                value.unsafety,
+                "unsafe implementations are not supported",
+            ));
+        }
+
+        let self_ty = impl_self_ty(&value)
+            .cloned()
+            .ok_or_else(|| syn::Error::new_spanned(&value.self_ty, "unsupported type"));
+
+        // the methods returning `Self` can not be parsed without the type
+        let items = match &self_ty {
+            Ok(self_ty) => collect_all(value.items.into_iter().map(|item| match item {
+                syn::ImplItem::Fn(item) => parse_impl_item_fn(item, self_ty),
+                item => Err(syn::Error::new_spanned(item, "unsupported item")),
+            })),
+            Err(_) => Ok(vec![]),
+        };
+        let (self_ty, items) = errors.finish(join(self_ty, items))?;
+
+        let attrs = value.attrs;
+        let impl_token = value.impl_token;
+        let brace_token = value.brace_token;
+
+        Ok(ItemImpl {
+            attrs,
+            impl_token,
+            self_ty,
+            brace_token,
+            items,
         })
     }
 }
 
+fn impl_self_ty(value: &syn::ItemImpl) -> Option<&syn::Ident> {
+    match value.self_ty.as_ref() {
+        syn::Type::Path(ty) if ty.qself.is_none() => ty.path.get_ident(),
+        _ => None,
+    }
+}
+
+fn parse_impl_item_fn(value: syn::ImplItemFn, self_ty: &syn::Ident) -> syn::Result<ImplItemFn> {
+    let mut errors = Errors::default();
+    if value.defaultness.is_some() {
+        errors.push(syn::Error::new_spanned(
+            value.defaultness,
+            "default functions are not supported",
+        ));
+    }
+
+    // `free` is used to release the handle
+    if value.sig.ident == "free" {
+        errors.push(syn::Error::new_spanned(
+            &value.sig.ident,
+            "`free` is a reserved method name",
+        ));
+    }
+
+    let attrs = value.attrs;
+    let vis = visibility_pub(&value.vis, value.sig.span());
+    let mut sig = errors.finish(Signature::try_from(value.sig))?;
+    let block = value.block;
+
+    // methods returning `Self` create a new handle
+    if let ReturnType::Type(_, ty) = &mut sig.output {
+        let is_self_ty = matches!(
+            ty.ty.as_ref(),
+            syn::Type::Path(path) if path.qself.is_none()
+                && (path.path.is_ident("Self") || path.path.is_ident(self_ty))
+        );
+        if is_self_ty {
+            *ty.ty = parse_quote!(#self_ty);
+            ty.kind = TypeKind::Opaque;
+            ty.ts = {
+                let name = self_ty.to_string();
+                typescript::Type![name]
+            };
+        }
+    }
+
+    Ok(ImplItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    })
+}
+
 impl TryFrom<syn::ItemFn> for ItemFn {
     type Error = syn::Error;
 
     fn try_from(value: syn::ItemFn) -> syn::Result<Self> {
+        let mut errors = Errors::default();
+        if let Some(receiver) = value.sig.receiver() {
+            errors.push(syn::Error::new_spanned(
+                receiver,
+                "self argument is not supported",
+            ));
+        }
+
         let attrs = value.attrs;
         let vis = visibility_pub(&value.vis, value.sig.span());
-        let sig = value.sig.try_into()?;
+        let sig = errors.finish(Signature::try_from(value.sig))?;
         let block = value.block;
 
         Ok(ItemFn {
@@ -121,109 +554,206 @@ impl TryFrom<syn::ItemFn> for ItemFn {
     }
 }
 
-impl TryFrom<syn::Field> for Field {
-    type Error = syn::Error;
+fn parse_fields(
+    value: syn::Fields,
+    visibility: impl Fn(&syn::Field) -> Option<Token![pub]>,
+) -> syn::Result<Fields> {
+    let parse_punctuated = |input: Punctuated<syn::Field, Token![,]>| {
+        collect_all(input.into_pairs().map(|pair| {
+            let (field, punct) = pair.into_tuple();
+            parse_field(field, &visibility).map(|field| Pair::new(field, punct))
+        }))
+    };
 
-    fn try_from(value: syn::Field) -> syn::Result<Self> {
-        let attrs = value.attrs;
-        let vis = visibility_pub(&value.vis, value.ident.span());
-        let ident = value.ident.unwrap();
-        let colon_token = value.colon_token.unwrap();
-        let ty = Type::try_from(&value.ty)?;
+    match value {
+        syn::Fields::Named(fields) => Ok(Fields::Named(FieldsNamed {
+            brace_token: fields.brace_token,
+            named: parse_punctuated(fields.named)?,
+        })),
+        syn::Fields::Unnamed(fields) => Ok(Fields::Unnamed(FieldsUnnamed {
+            paren_token: fields.paren_token,
+            unnamed: parse_punctuated(fields.unnamed)?,
+        })),
+        syn::Fields::Unit => Ok(Fields::Unit),
+    }
+}
 
-        Ok(Field {
-            attrs,
-            vis,
-            ident,
-            colon_token,
-            ty,
-        })
+fn parse_field(
+    value: syn::Field,
+    visibility: impl Fn(&syn::Field) -> Option<Token![pub]>,
+) -> syn::Result<Field> {
+    let vis = visibility(&value);
+    let unconditional = check_unconditional(&value.attrs);
+    let serde = Serde::parse(&value.attrs);
+    let attrs = value.attrs;
+    let ident = value.ident;
+    let colon_token = value.colon_token;
+    let ty = Type::try_from(&value.ty).and_then(json_type);
+    let ((_, serde), ty) = join(join(unconditional, serde), ty)?;
+
+    Ok(Field {
+        attrs,
+        vis,
+        ident,
+        colon_token,
+        ty,
+        serde,
+    })
+}
+
+/// The metadata stored in the library are not evaluated by the compiler, the fields and the
+/// variants enabled by a feature would be described even if they are not compiled
+fn check_unconditional(attrs: &[syn::Attribute]) -> syn::Result<()> {
+    let mut errors = Errors::default();
+    for attr in attrs {
+        if attr.path().is_ident("cfg") || attr.path().is_ident("cfg_attr") {
+            errors.push(syn::Error::new_spanned(
+                attr,
+                "conditional attributes are supported only on items and methods",
+            ));
+        }
+    }
+    errors.finish(Ok(()))
+}
+
+/// The `serde` attributes of the items are described by the metadata too, they can not depend
+/// on the selected features
+fn check_unconditional_serde(attrs: &[syn::Attribute]) -> syn::Result<()> {
+    let mut errors = Errors::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("cfg_attr")) {
+        let Ok(metas) = attr.parse_args_with(Punctuated::<syn::Meta, Token![,]>::parse_terminated)
+        else {
+            continue;
+        };
+        if metas
+            .iter()
+            .skip(1)
+            .any(|meta| meta.path().is_ident("serde"))
+        {
+            errors.push(syn::Error::new_spanned(
+                attr,
+                "`serde` attributes can not be conditional",
+            ));
+        }
     }
+    errors.finish(Ok(()))
 }
 
 impl TryFrom<syn::Signature> for Signature {
     type Error = syn::Error;
 
     fn try_from(value: syn::Signature) -> syn::Result<Signature> {
+        let mut errors = Errors::default();
         if value.constness.is_some() {
-            return Err(syn::Error::new_spanned(
+            errors.push(syn::Error::new_spanned(
                 value.constness,
                 "const functions are not supported",
             ));
         }
-        if value.asyncness.is_some() {
-            return Err(syn::Error::new_spanned(
-                value.asyncness,
-                "async functions are not supported",
-            ));
-        }
         if value.unsafety.is_some() {
-            return Err(syn::Error::new_spanned(
+            errors.push(syn::Error::new_spanned(
                 value.unsafety,
                 "unsafe functions are not supported",
             ));
         }
         if value.abi.is_some() {
             // variadic argument is allowed only in extern function, then it should not be checked
-            return Err(syn::Error::new_spanned(
+            errors.push(syn::Error::new_spanned(
                 value.abi,
                 "extern functions are not supported",
             ));
         }
         if !value.generics.params.is_empty() {
-            return Err(syn::Error::new_spanned(
+            errors.push(syn::Error::new_spanned(
                 value.generics,
                 "function parameters are not supported",
             ));
         }
 
+        let asyncness = value.asyncness;
         let fn_token = value.fn_token;
         let ident = value.ident;
         let paren_token = value.paren_token;
 
-        let mut inputs = Punctuated::new();
-        for pair in value.inputs.into_pairs() {
+        let mut receiver = Ok(None);
+        let inputs = collect_all(value.inputs.into_pairs().filter_map(|pair| {
             let (fn_arg, punct) = pair.into_tuple();
-
-            let fn_arg = fn_arg.try_into()?;
-            inputs.push_value(fn_arg);
-
-            if let Some(punct) = punct {
-                inputs.push_punct(punct);
+            match fn_arg {
+                syn::FnArg::Receiver(value) => {
+                    receiver = Receiver::try_from(value).map(Some);
+                    None
+                }
+                fn_arg => Some(FnArg::try_from(fn_arg).map(|fn_arg| Pair::new(fn_arg, punct))),
             }
-        }
+        }));
+        let output = ReturnType::try_from(value.output);
 
-        let output = value.output.try_into()?;
+        let ((receiver, inputs), output) = errors.finish(join(join(receiver, inputs), output))?;
 
         Ok(Signature {
+            asyncness,
             fn_token,
             ident,
             paren_token,
+            receiver,
             inputs,
             output,
         })
     }
 }
 
+impl TryFrom<syn::Receiver> for Receiver {
+    type Error = syn::Error;
+
+    fn try_from(value: syn::Receiver) -> syn::Result<Receiver> {
+        let Some((and_token, _)) = value.reference else {
+            return Err(syn::Error::new_spanned(
+                value,
+                "self argument must be borrowed, use `&self` or `&mut self`",
+            ));
+        };
+        if value.colon_token.is_some() {
+            return Err(syn::Error::new_spanned(
+                value,
+                "typed self argument is not supported",
+            ));
+        }
+
+        let mutability = value.mutability;
+        let self_token = value.self_token;
+
+        Ok(Receiver {
+            and_token,
+            mutability,
+            self_token,
+        })
+    }
+}
+
 impl TryFrom<syn::FnArg> for FnArg {
     type Error = syn::Error;
 
     fn try_from(value: syn::FnArg) -> syn::Result<FnArg> {
         let syn::FnArg::Typed(fn_arg) = value else {
-            return Err(syn::Error::new_spanned(value, "self argument is not supported"));
+            return Err(syn::Error::new_spanned(
+                value,
+                "self argument is not supported",
+            ));
         };
 
-        let (mutability, ident) = match fn_arg.pat.as_ref() {
-            Pat::Ident(pat) => (pat.mutability, pat.ident.clone()),
-            _ => {
-                return Err(syn::Error::new_spanned(
-                    fn_arg,
-                    "pattern matching is not supported",
-                ))
-            }
+        let pat = match fn_arg.pat.as_ref() {
+            Pat::Ident(pat) => Ok((pat.mutability, pat.ident.clone())),
+            pat => Err(syn::Error::new_spanned(
+                pat,
+                "pattern matching is not supported",
+            )),
         };
         let colon_token = fn_arg.colon_token;
-        let ty = Type::try_from(fn_arg.ty.as_ref())?;
+        let ty = match parse_callback_type(fn_arg.ty.as_ref()) {
+            Some(ty) => ty,
+            None => Type::try_from(fn_arg.ty.as_ref()),
+        };
+        let ((mutability, ident), ty) = join(pat, ty)?;
 
         Ok(FnArg {
             mutability,
@@ -234,32 +764,339 @@ impl TryFrom<syn::FnArg> for FnArg {
     }
 }
 
-impl TryFrom<syn::ReturnType> for ReturnType {
-    type Error = syn::Error;
+/// Parse the type of a callback argument, `None` is returned if the type is not a function
+fn parse_callback_type(value: &syn::Type) -> Option<syn::Result<Type>> {
+    let (mode, inputs, output) = match value {
+        syn::Type::ImplTrait(ty) => {
+            let (inputs, output) = parse_callback_bounds(&ty.bounds)?;
+            if let Err(err) = check_callback_lifetime(&ty.bounds) {
+                return Some(Err(err));
+            }
+            (CallbackMode::Impl, inputs, output)
+        }
+        syn::Type::Reference(ty) => {
+            let syn::Type::TraitObject(elem) = ty.elem.as_ref() else {
+                return None;
+            };
+            let (inputs, output) = parse_callback_bounds(&elem.bounds)?;
+            if let Err(err) = check_callback_lifetime(&elem.bounds) {
+                return Some(Err(err));
+            }
+            let mode = match ty.mutability {
+                Some(_) => CallbackMode::DynMut,
+                None => CallbackMode::Dyn,
+            };
+            (mode, inputs, output)
+        }
+        syn::Type::BareFn(ty) => {
+            let is_extern_c = ty
+                .abi
+                .as_ref()
+                .is_some_and(|abi| abi.name.as_ref().is_none_or(|name| name.value() == "C"));
+            if !is_extern_c {
+                return Some(Err(syn::Error::new_spanned(
+                    ty,
+                    "function pointers must be `extern \"C\"`",
+                )));
+            }
+            if ty.variadic.is_some() {
+                return Some(Err(syn::Error::new_spanned(
+                    &ty.variadic,
+                    "variadic functions are not supported",
+                )));
+            }
+            let inputs = ty.inputs.iter().map(|arg| &arg.ty).collect();
+            (CallbackMode::Pointer, inputs, &ty.output)
+        }
+        _ => return None,
+    };
 
-    fn try_from(value: syn::ReturnType) -> syn::Result<ReturnType> {
-        let syn::ReturnType::Type(rarrow, ref ty) = value else {
-            return Ok(ReturnType::Default)
+    let parse = || {
+        let inputs = collect_all::<_, Vec<_>>(inputs.into_iter().map(|input| {
+            let ty = Type::try_from(input)?;
+            match ty.kind {
+                TypeKind::Native(_) => Ok(ty),
+                TypeKind::StringBorrowed | TypeKind::StringOwned | TypeKind::Json
+                    if !matches!(mode, CallbackMode::Pointer) && !ty.is_result() =>
+                {
+                    Ok(ty)
+                }
+                _ => Err(syn::Error::new_spanned(
+                    input,
+                    "unsupported type of callback argument",
+                )),
+            }
+        }));
+        let output = match output {
+            syn::ReturnType::Type(_, ty) if !is_unit(ty) => {
+                Type::try_from(ty.as_ref()).and_then(|output| match output.kind {
+                    TypeKind::Native(_) => Ok(Some(output)),
+                    _ => Err(syn::Error::new_spanned(
+                        ty,
+                        "unsupported type of callback result",
+                    )),
+                })
+            }
+            _ => Ok(None),
         };
+        let (inputs, output) = join(inputs, output)?;
 
-        let ty = Type::try_from(ty.as_ref())?;
-        let return_type = ReturnType::Type(rarrow, ty);
-        Ok(return_type)
-    }
-}
-
-impl TryFrom<&syn::Type> for Type {
+        let ts = match mode {
+            CallbackMode::Pointer => {
+                let parameters = inputs
+                    .iter()
+                    .map(|input| match input.kind {
+                        TypeKind::Native(ty) => format!("\"{}\"", ty.symbol()),
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>();
+                let result = match &output {
+                    Some(Type {
+                        kind: TypeKind::Native(ty),
+                        ..
+                    }) => ty.symbol(),
+                    _ => "void",
+                };
+                format!(
+                    "Deno.UnsafeCallback<{{ readonly parameters: readonly [{}]; readonly result: \"{}\" }}>",
+                    parameters.join(", "),
+                    result
+                )
+            }
+            _ => {
+                let parameters = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, input)| format!("arg{}: {}", index, input.ts))
+                    .collect::<Vec<_>>();
+                let result = match &output {
+                    Some(output) => output.ts.to_string(),
+                    None => "void".to_owned(),
+                };
+                format!("({}) => {}", parameters.join(", "), result)
+            }
+        };
+        let ts = typescript::Type![ts];
+
+        let ty = Box::new(value.clone());
+        let kind = TypeKind::Callback(Box::new(TypeCallback {
+            mode,
+            inputs,
+            output,
+        }));
+        let error = None;
+
+        Ok(Type {
+            ty,
+            kind,
+            ts,
+            error,
+        })
+    };
+    Some(parse())
+}
+
+/// Callbacks are released when the call returns, they can not be stored
+fn check_callback_lifetime(bounds: &Punctuated<syn::TypeParamBound, Token![+]>) -> syn::Result<()> {
+    for bound in bounds {
+        if let syn::TypeParamBound::Lifetime(lifetime) = bound {
+            if lifetime.ident == "static" {
+                return Err(syn::Error::new_spanned(
+                    lifetime,
+                    "callbacks are released when the call returns, they can not be 'static",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The arguments and the result of `Fn`, `FnMut` or `FnOnce` bounds
+fn parse_callback_bounds(
+    bounds: &Punctuated<syn::TypeParamBound, Token![+]>,
+) -> Option<(Vec<&syn::Type>, &syn::ReturnType)> {
+    bounds.iter().find_map(|bound| {
+        let syn::TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let segment = bound.path.segments.last()?;
+        if !["Fn", "FnMut", "FnOnce"]
+            .iter()
+            .any(|name| segment.ident == name)
+        {
+            return None;
+        }
+        let syn::PathArguments::Parenthesized(arguments) = &segment.arguments else {
+            return None;
+        };
+        Some((arguments.inputs.iter().collect(), &arguments.output))
+    })
+}
+
+impl TryFrom<syn::ReturnType> for ReturnType {
+    type Error = syn::Error;
+
+    fn try_from(value: syn::ReturnType) -> syn::Result<ReturnType> {
+        let syn::ReturnType::Type(rarrow, ref ty) = value else {
+            return Ok(ReturnType::Default);
+        };
+        // -> ()
+        if is_unit(ty) {
+            return Ok(ReturnType::Default);
+        }
+        // the callbacks are released when the call returns
+        if parse_callback_type(ty).is_some() {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "callbacks are supported only as arguments",
+            ));
+        }
+
+        let ty = Type::try_from(ty.as_ref())?;
+        let return_type = ReturnType::Type(rarrow, ty);
+        Ok(return_type)
+    }
+}
+
+impl TryFrom<&syn::Type> for Type {
     type Error = syn::Error;
 
     fn try_from(value: &syn::Type) -> syn::Result<Self> {
-        match value {
+        let ty = match value {
             syn::Type::Path(ty) => parse_type_path(ty),
             syn::Type::Reference(ty) => parse_type_reference(ty),
+            syn::Type::Array(ty) => parse_type_array(ty),
+            syn::Type::Tuple(ty) => parse_type_tuple(ty),
             _ => Err(syn::Error::new_spanned(value, "unsupported type")),
+        };
+        ty.map_err(|err| match suggest_type(value) {
+            Some(suggestion) => {
+                syn::Error::new_spanned(value, format!("unsupported type, {}", suggestion))
+            }
+            None => err,
+        })
+    }
+}
+
+/// Suggest the closest supported types, e.g. "use `&[u8]` or `Vec<u8>` instead of `&Vec<u8>`"
+fn suggest_type(value: &syn::Type) -> Option<String> {
+    let candidates: Vec<syn::Type> = match value {
+        syn::Type::Reference(ty) => {
+            let mutability = ty.mutability;
+            match ty.elem.as_ref() {
+                syn::Type::Path(elem) if elem.qself.is_none() => {
+                    let segment = elem.path.segments.last()?;
+                    match (segment.ident.to_string().as_str(), type_argument(segment)) {
+                        ("Vec", Some(arg)) => {
+                            vec![parse_quote!(&#mutability [#arg]), parse_quote!(Vec<#arg>)]
+                        }
+                        ("String", _) if mutability.is_none() => {
+                            vec![parse_quote!(&str), parse_quote!(String)]
+                        }
+                        ("String" | "str", _) => vec![parse_quote!(String)],
+                        _ => vec![syn::Type::Path(elem.clone())],
+                    }
+                }
+                syn::Type::Slice(elem) => {
+                    let elem = &elem.elem;
+                    vec![parse_quote!(Vec<#elem>)]
+                }
+                syn::Type::Array(elem) => vec![syn::Type::Array(elem.clone())],
+                _ => vec![],
+            }
         }
+        syn::Type::Path(ty) if ty.qself.is_none() => {
+            let segments = &ty.path.segments;
+            let segment = segments.last()?;
+            if segments.len() == 1 {
+                match (segment.ident.to_string().as_str(), type_argument(segment)) {
+                    ("Box", Some(syn::Type::Slice(arg))) => {
+                        let elem = &arg.elem;
+                        vec![parse_quote!(Vec<#elem>)]
+                    }
+                    ("Box", Some(arg)) => vec![arg.clone()],
+                    _ => vec![],
+                }
+            } else {
+                // the types of the standard library are recognized only by their name, the
+                // types defined elsewhere have to be imported
+                let is_std = ["std", "core", "alloc"]
+                    .iter()
+                    .any(|name| segments[0].ident == name);
+                let is_known = [
+                    "Box", "Option", "Result", "String", "Vec", "HashMap", "BTreeMap", "HashSet",
+                    "BTreeSet",
+                ]
+                .iter()
+                .any(|name| segment.ident == name)
+                    || segment.ident.to_string().starts_with("NonZero");
+                if is_std && !is_known {
+                    return None;
+                }
+                vec![syn::Type::Path(syn::TypePath {
+                    qself: None,
+                    path: segment.clone().into(),
+                })]
+            }
+        }
+        syn::Type::Ptr(ty) => {
+            let mutability = ty.mutability;
+            let elem = &ty.elem;
+            vec![parse_quote!(&#mutability [#elem]), elem.as_ref().clone()]
+        }
+        syn::Type::Slice(ty) => {
+            let elem = &ty.elem;
+            vec![parse_quote!(Vec<#elem>)]
+        }
+        _ => vec![],
+    };
+
+    let candidates = candidates
+        .iter()
+        .filter(|candidate| Type::try_from(*candidate).is_ok())
+        .map(|candidate| format!("`{}`", type_name(candidate)))
+        .collect::<Vec<_>>();
+    let (last, rest) = candidates.split_last()?;
+    let candidates = match rest {
+        [] => last.clone(),
+        rest => format!("{} or {}", rest.join(", "), last),
+    };
+    Some(format!(
+        "use {} instead of `{}`",
+        candidates,
+        type_name(value)
+    ))
+}
+
+/// The only type argument of a path segment, e.g. `T` of `Vec<T>`
+fn type_argument(segment: &syn::PathSegment) -> Option<&syn::Type> {
+    let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.iter().collect::<Vec<_>>().as_slice() {
+        [syn::GenericArgument::Type(ty)] => Some(ty),
+        _ => None,
     }
 }
 
+/// The type as written in the source code, e.g. `&Vec<u8>` instead of `& Vec < u8 >`
+fn type_name(value: &impl ToTokens) -> String {
+    [
+        (" :: ", "::"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        ("& ", "&"),
+        ("* ", "*"),
+    ]
+    .iter()
+    .fold(value.to_token_stream().to_string(), |name, (from, to)| {
+        name.replace(from, to)
+    })
+}
+
 fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
     let segments = &value.path.segments;
 
@@ -267,9 +1104,10 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
         let ty = Box::new(syn::Type::Path(value.clone()));
 
         let segment = &segments[0];
-        let is_result = segment.ident == "Result";
+        let mut error = None;
         let (kind, ts) = match segment.ident.to_string().as_str() {
             // native types
+            "bool" => (TypeKind::Native(TypeNative::Bool), typescript::boolean),
             "i8" => (TypeKind::Native(TypeNative::I8), typescript::number),
             "i16" => (TypeKind::Native(TypeNative::I16), typescript::number),
             "i32" => (TypeKind::Native(TypeNative::I32), typescript::number),
@@ -282,8 +1120,8 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
                 typescript::number | typescript::bigint,
             ),
             "u8" => (TypeKind::Native(TypeNative::U8), typescript::number),
-            "u16" => (TypeKind::Native(TypeNative::I16), typescript::number),
-            "u32" => (TypeKind::Native(TypeNative::I32), typescript::number),
+            "u16" => (TypeKind::Native(TypeNative::U16), typescript::number),
+            "u32" => (TypeKind::Native(TypeNative::U32), typescript::number),
             "u64" => (
                 TypeKind::Native(TypeNative::U64),
                 typescript::number | typescript::bigint,
@@ -294,11 +1132,22 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
             ),
             "f32" => (TypeKind::Native(TypeNative::F32), typescript::number),
             "f64" => (TypeKind::Native(TypeNative::F64), typescript::number),
+            "i128" => (TypeKind::I128, typescript::bigint),
+            "u128" => (TypeKind::U128, typescript::bigint),
+            // a string containing a single character
+            "char" => (TypeKind::Json, typescript::string),
+            s if s.starts_with("NonZero") => parse_nonzero_type(segment)?,
             "Box" => parse_pointer_type(segment)?,
             "Option" => parse_option_type(segment)?,
-            "Result" => parse_result_type(segment)?,
+            "Result" => {
+                let (kind, ts, err) = parse_result_type(segment)?;
+                error = Some(Box::new(err));
+                (kind, ts)
+            }
             "String" => (TypeKind::StringOwned, typescript::string),
             "Vec" => parse_vector_type(segment)?,
+            "HashMap" | "BTreeMap" => parse_map_type(segment)?,
+            "HashSet" | "BTreeSet" => parse_set_type(segment)?,
             s => (TypeKind::Json, typescript::Type![s]),
         };
 
@@ -306,7 +1155,7 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
             ty,
             kind,
             ts,
-            is_result,
+            error,
         });
     }
 
@@ -315,7 +1164,7 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
         let ty = Box::new(syn::Type::Path(value.clone()));
 
         let segment = &segments[2];
-        let is_result = segment.ident == "Result";
+        let mut error = None;
         let (kind, ts) = if (segments[0].ident == "std" || segments[0].ident == "alloc")
             && segments[1].ident == "box"
             && segment.ident == "Box"
@@ -330,12 +1179,29 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
             && segments[1].ident == "result"
             && segment.ident == "Result"
         {
-            parse_result_type(segment)?
+            let (kind, ts, err) = parse_result_type(segment)?;
+            error = Some(Box::new(err));
+            (kind, ts)
         } else if (segments[0].ident == "std" || segments[0].ident == "alloc")
             && segments[1].ident == "vec"
             && segment.ident == "Vec"
         {
             parse_vector_type(segment)?
+        } else if (segments[0].ident == "std" || segments[0].ident == "core")
+            && segments[1].ident == "num"
+            && segment.ident.to_string().starts_with("NonZero")
+        {
+            parse_nonzero_type(segment)?
+        } else if segments[0].ident == "std"
+            && segments[1].ident == "collections"
+            && (segment.ident == "HashMap" || segment.ident == "BTreeMap")
+        {
+            parse_map_type(segment)?
+        } else if segments[0].ident == "std"
+            && segments[1].ident == "collections"
+            && (segment.ident == "HashSet" || segment.ident == "BTreeSet")
+        {
+            parse_set_type(segment)?
         } else {
             return Err(syn::Error::new_spanned(value, "unsupported type"));
         };
@@ -344,7 +1210,7 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
             ty,
             kind,
             ts,
-            is_result,
+            error,
         });
     }
 
@@ -353,7 +1219,7 @@ fn parse_type_path(value: &syn::TypePath) -> syn::Result<Type> {
 
 fn parse_type_reference(input: &syn::TypeReference) -> syn::Result<Type> {
     let elem = &*input.elem;
-    let is_result = false;
+    let error = None;
     match elem {
         syn::Type::Path(ty) => {
             let path = &ty.path;
@@ -373,7 +1239,7 @@ fn parse_type_reference(input: &syn::TypeReference) -> syn::Result<Type> {
                         ty,
                         kind,
                         ts,
-                        is_result,
+                        error,
                     });
                 }
             }
@@ -409,17 +1275,210 @@ fn parse_type_reference(input: &syn::TypeReference) -> syn::Result<Type> {
                         ty,
                         kind,
                         ts,
-                        is_result,
+                        error,
                     });
                 }
             }
         }
+        syn::Type::Array(ty) => {
+            // &[xx; N] and &mut [xx; N]
+            if let Some((elem_type, ts)) = native_array_elem(&ty.elem) {
+                let len = parse_array_len(&ty.len)?;
+                let ty = Box::new(syn::Type::Reference(input.clone()));
+                let kind = if input.mutability.is_none() {
+                    TypeKind::ArrayBorrowed(elem_type, len)
+                } else {
+                    TypeKind::ArrayBorrowedMut(elem_type, len)
+                };
+
+                return Ok(Type {
+                    ty,
+                    kind,
+                    ts,
+                    error,
+                });
+            }
+        }
         _ => {}
     }
 
     Err(syn::Error::new_spanned(input, "unsupported type"))
 }
 
+fn parse_type_array(value: &syn::TypeArray) -> syn::Result<Type> {
+    let ty = Box::new(syn::Type::Array(value.clone()));
+    let len = parse_array_len(&value.len)?;
+    let error = None;
+
+    // [xx; N]
+    if let Some((elem_type, ts)) = native_array_elem(&value.elem) {
+        let kind = TypeKind::ArrayOwned(elem_type, len);
+        return Ok(Type {
+            ty,
+            kind,
+            ts,
+            error,
+        });
+    }
+
+    // [T; N] (where T is a valid type)
+    let elem = json_type(Type::try_from(&*value.elem)?)?;
+    Ok(Type {
+        ty,
+        kind: TypeKind::Json,
+        ts: elem.ts.array(),
+        error,
+    })
+}
+
+fn is_unit(value: &syn::Type) -> bool {
+    matches!(value, syn::Type::Tuple(ty) if ty.elems.is_empty())
+}
+
+fn parse_array_len(value: &syn::Expr) -> syn::Result<usize> {
+    match value {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(len),
+            ..
+        }) => len.base10_parse(),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            "the length of the array must be an integer literal",
+        )),
+    }
+}
+
+/// The element of an array passed as a typed array
+fn native_array_elem(value: &syn::Type) -> Option<(TypeNative, typescript::Type)> {
+    let syn::Type::Path(elem) = value else {
+        return None;
+    };
+    if elem.qself.is_some() {
+        return None;
+    }
+    let ident = elem.path.get_ident()?.to_string();
+    let elem = match ident.as_str() {
+        "i8" => (TypeNative::I8, typescript::Int8Array),
+        "i16" => (TypeNative::I16, typescript::Int16Array),
+        "i32" => (TypeNative::I32, typescript::Int32Array),
+        "i64" => (TypeNative::I64, typescript::BigInt64Array),
+        "u8" => (TypeNative::U8, typescript::Uint8Array),
+        "u16" => (TypeNative::U16, typescript::Uint16Array),
+        "u32" => (TypeNative::U32, typescript::Uint32Array),
+        "u64" => (TypeNative::U64, typescript::BigUint64Array),
+        "f32" => (TypeNative::F32, typescript::Float32Array),
+        "f64" => (TypeNative::F64, typescript::Float64Array),
+        _ => return None,
+    };
+    Some(elem)
+}
+
+fn parse_type_tuple(value: &syn::TypeTuple) -> syn::Result<Type> {
+    // () (encoded as null)
+    if value.elems.is_empty() {
+        return Ok(Type {
+            ty: Box::new(syn::Type::Tuple(value.clone())),
+            kind: TypeKind::Json,
+            ts: typescript::void,
+            error: None,
+        });
+    }
+
+    // (T1, T2, ..) (where all Ti are valid types), encoded as an array
+    let members = collect_all(
+        value
+            .elems
+            .iter()
+            .map(|elem| Type::try_from(elem).and_then(json_type).map(|elem| elem.ts)),
+    )?;
+
+    Ok(Type {
+        ty: Box::new(syn::Type::Tuple(value.clone())),
+        kind: TypeKind::Json,
+        ts: typescript::Type::tuple(members),
+        error: None,
+    })
+}
+
+/// The type used when the value is encoded as JSON, e.g. as a field of a struct, arrays are
+/// encoded as arrays of numbers instead of typed arrays
+fn json_type(value: Type) -> syn::Result<Type> {
+    let ty = match value.kind {
+        TypeKind::ArrayBorrowed(elem, _)
+        | TypeKind::ArrayBorrowedMut(elem, _)
+        | TypeKind::ArrayOwned(elem, _)
+        | TypeKind::BufferBorrowed(elem)
+        | TypeKind::BufferBorrowedMut(elem)
+        | TypeKind::BufferOwned(elem) => {
+            let ts = match elem {
+                TypeNative::I64 | TypeNative::ISize | TypeNative::U64 | TypeNative::USize => {
+                    typescript::number | typescript::bigint
+                }
+                _ => typescript::number,
+            };
+            Type {
+                kind: TypeKind::Json,
+                ts: ts.array(),
+                ..value
+            }
+        }
+        // JSON numbers are doubles, they would lose the precision of large integers
+        TypeKind::I128 | TypeKind::U128 => {
+            return Err(syn::Error::new_spanned(
+                &value.ty,
+                "unsupported type, 128-bit integers are supported only as arguments and results",
+            ));
+        }
+        TypeKind::NonZero(_) => Type {
+            kind: TypeKind::Json,
+            ..value
+        },
+        _ => value,
+    };
+    Ok(ty)
+}
+
+fn parse_nonzero_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescript::Type)> {
+    let ident = value.ident.to_string();
+    let elem = match ident.strip_prefix("NonZero") {
+        // NonZero<T>
+        Some("") => match &value.arguments {
+            syn::PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+                match &arguments.args[0] {
+                    syn::GenericArgument::Type(syn::Type::Path(ty)) => {
+                        ty.path.get_ident().map(|ident| ident.to_string())
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        },
+        // NonZeroU32 and the other aliases
+        Some(elem) if value.arguments.is_none() => Some(elem.to_ascii_lowercase()),
+        _ => None,
+    };
+    let (elem, ts) = match elem.as_deref() {
+        Some("i8") => (TypeNative::I8, typescript::number),
+        Some("i16") => (TypeNative::I16, typescript::number),
+        Some("i32") => (TypeNative::I32, typescript::number),
+        Some("i64") => (TypeNative::I64, typescript::number | typescript::bigint),
+        Some("isize") => (TypeNative::ISize, typescript::number | typescript::bigint),
+        Some("u8") => (TypeNative::U8, typescript::number),
+        Some("u16") => (TypeNative::U16, typescript::number),
+        Some("u32") => (TypeNative::U32, typescript::number),
+        Some("u64") => (TypeNative::U64, typescript::number | typescript::bigint),
+        Some("usize") => (TypeNative::USize, typescript::number | typescript::bigint),
+        _ => {
+            // unknown types starting with NonZero are types of the module
+            if value.arguments.is_none() {
+                return Ok((TypeKind::Json, typescript::Type![ident]));
+            }
+            return Err(syn::Error::new_spanned(value, "unsupported type"));
+        }
+    };
+    Ok((TypeKind::NonZero(elem), ts))
+}
+
 fn parse_pointer_type(ident: &syn::PathSegment) -> syn::Result<(TypeKind, typescript::Type)> {
     assert!(ident.ident == "Box");
 
@@ -450,8 +1509,8 @@ fn parse_pointer_type(ident: &syn::PathSegment) -> syn::Result<(TypeKind, typesc
                                 "u16" => (TypeNative::U16, typescript::Uint16Array),
                                 "u32" => (TypeNative::U32, typescript::Uint32Array),
                                 "u64" => (TypeNative::U64, typescript::BigUint64Array),
-                                "f32" => (TypeNative::I32, typescript::Float32Array),
-                                "f64" => (TypeNative::I64, typescript::Float64Array),
+                                "f32" => (TypeNative::F32, typescript::Float32Array),
+                                "f64" => (TypeNative::F64, typescript::Float64Array),
                                 _ => {
                                     return Err(syn::Error::new_spanned(ident, "unsupported type"))
                                 }
@@ -475,17 +1534,16 @@ fn parse_option_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescr
         let args = &arguments.args;
         if args.len() == 1 {
             // Option<T> (where T is a valid type)
-            if let syn::GenericArgument::Type(syn::Type::Path(ty)) = &args[0] {
-                if let Ok(elem) = parse_type_path(ty) {
-                    return Ok((TypeKind::Json, elem.ts | typescript::null));
-                }
+            if let syn::GenericArgument::Type(ty) = &args[0] {
+                let elem = Type::try_from(ty).and_then(json_type)?;
+                return Ok((TypeKind::Json, elem.ts | typescript::null));
             }
         }
     }
     Err(syn::Error::new_spanned(value, "unsupported type"))
 }
 
-fn parse_result_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescript::Type)> {
+fn parse_result_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescript::Type, Type)> {
     assert!(value.ident == "Result");
 
     let arguments = &value.arguments;
@@ -493,16 +1551,13 @@ fn parse_result_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescr
         let args = &arguments.args;
         if args.len() == 2 {
             // Result<T, E> (where both T and E are a valid types)
-            if let (
-                syn::GenericArgument::Type(syn::Type::Path(ok_ty)),
-                syn::GenericArgument::Type(syn::Type::Path(err_ty)),
-            ) = (&args[0], &args[1])
+            if let (syn::GenericArgument::Type(ok_ty), syn::GenericArgument::Type(err_ty)) =
+                (&args[0], &args[1])
             {
-                if let Ok(ok_ty) = parse_type_path(ok_ty) {
-                    if parse_type_path(err_ty).is_ok() {
-                        return Ok((TypeKind::Json, ok_ty.ts));
-                    }
-                }
+                let ok_ty = Type::try_from(ok_ty).and_then(json_type);
+                let err_ty = Type::try_from(err_ty).and_then(json_type);
+                let (ok_ty, err_ty) = join(ok_ty, err_ty)?;
+                return Ok((TypeKind::Json, ok_ty.ts, err_ty));
             }
         }
     }
@@ -530,25 +1585,326 @@ fn parse_vector_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescr
                         "u16" => (TypeNative::U16, typescript::Uint16Array),
                         "u32" => (TypeNative::U32, typescript::Uint32Array),
                         "u64" => (TypeNative::U64, typescript::BigUint64Array),
-                        "f32" => (TypeNative::I32, typescript::Float32Array),
-                        "f64" => (TypeNative::I64, typescript::Float64Array),
-                        _ => {
-                            // Vec<T> (where T is a valid type)
-                            if let Ok(elem) = parse_type_path(ty) {
-                                return Ok((TypeKind::Json, elem.ts.array()));
-                            } else {
-                                return Err(syn::Error::new_spanned(ident, "unsupported type"));
-                            }
-                        }
+                        "f32" => (TypeNative::F32, typescript::Float32Array),
+                        "f64" => (TypeNative::F64, typescript::Float64Array),
+                        _ => return parse_json_array(&args[0]),
                     };
                     return Ok((TypeKind::BufferOwned(elem_type), ts));
                 }
             }
+            // Vec<T> (where T is a valid type)
+            return parse_json_array(&args[0]);
         }
     }
     Err(syn::Error::new_spanned(value, "unsupported type"))
 }
 
+fn parse_map_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescript::Type)> {
+    assert!(value.ident == "HashMap" || value.ident == "BTreeMap");
+
+    let arguments = &value.arguments;
+    if let syn::PathArguments::AngleBracketed(arguments) = arguments {
+        let args = &arguments.args;
+        if args.len() == 2 {
+            // HashMap<K, V> (where K is a valid key and V is a valid type)
+            if let (syn::GenericArgument::Type(key_ty), syn::GenericArgument::Type(value_ty)) =
+                (&args[0], &args[1])
+            {
+                let key = parse_map_key(key_ty);
+                let value_ty = Type::try_from(value_ty).and_then(json_type);
+                let (key, value_ty) = join(key, value_ty)?;
+                return Ok((TypeKind::Json, typescript::Type::record(key, value_ty.ts)));
+            }
+        }
+    }
+    Err(syn::Error::new_spanned(value, "unsupported type"))
+}
+
+/// Maps are encoded as JSON objects, the keys are always serialized as strings
+fn parse_map_key(value: &syn::Type) -> syn::Result<typescript::Type> {
+    let unsupported = || unsupported_map_key(value);
+    let syn::Type::Path(path) = value else {
+        return Err(unsupported());
+    };
+    let key = parse_type_path(path)?;
+    match key.kind {
+        TypeKind::StringOwned => Ok(typescript::string),
+        TypeKind::Native(TypeNative::Bool | TypeNative::F32 | TypeNative::F64) => {
+            Err(unsupported())
+        }
+        // large integers are not converted to numbers, they would lose precision
+        TypeKind::I128 | TypeKind::U128 => Ok(typescript::string),
+        TypeKind::Native(ty) | TypeKind::NonZero(ty) => Ok(match ty {
+            TypeNative::I64 | TypeNative::ISize | TypeNative::U64 | TypeNative::USize => {
+                typescript::string
+            }
+            _ => typescript::number,
+        }),
+        // char
+        TypeKind::Json if key.ts == typescript::string => Ok(key.ts),
+        // unit-only enums, encoded as strings, the module checks that the type is really an enum
+        // without fields (see `check_map_keys`)
+        TypeKind::Json if matches!(key.ts, typescript::Type::Named(_)) => Ok(key.ts),
+        _ => Err(unsupported()),
+    }
+}
+
+fn unsupported_map_key(value: &syn::Type) -> syn::Error {
+    let message = format!(
+        "unsupported map key, use `String`, an integer or a unit-only enum instead of `{}`",
+        type_name(value)
+    );
+    syn::Error::new_spanned(value, message)
+}
+
+/// The types of the fields, the arguments and the results of an item
+fn item_types(item: &Item) -> Vec<&Type> {
+    match item {
+        Item::Enum(item) => item
+            .variants
+            .iter()
+            .flat_map(|variant| variant.fields.iter().map(|field| &field.ty))
+            .collect(),
+        Item::Fn(item) => signature_types(&item.sig),
+        Item::Impl(item) => item
+            .items
+            .iter()
+            .flat_map(|method| signature_types(&method.sig))
+            .collect(),
+        Item::Opaque(_) => vec![],
+        Item::Struct(item) => item.fields.iter().map(|field| &field.ty).collect(),
+    }
+}
+
+fn signature_types(sig: &Signature) -> Vec<&Type> {
+    let output = match &sig.output {
+        ReturnType::Type(_, ty) => Some(ty),
+        ReturnType::Default => None,
+    };
+    sig.inputs
+        .iter()
+        .map(|input| &input.ty)
+        .chain(output)
+        .collect()
+}
+
+fn item_signatures(item: &Item) -> Vec<&Signature> {
+    match item {
+        Item::Fn(item) => vec![&item.sig],
+        Item::Impl(item) => item.items.iter().map(|method| &method.sig).collect(),
+        _ => vec![],
+    }
+}
+
+/// The types of the values of the item, i.e. all the types except the errors of the results and
+/// the returned handles
+fn value_types(item: &Item) -> Vec<&syn::Type> {
+    match item {
+        Item::Fn(_) | Item::Impl(_) => item_signatures(item)
+            .into_iter()
+            .flat_map(|sig| {
+                let output = match &sig.output {
+                    ReturnType::Type(_, ty) if matches!(ty.kind, TypeKind::Opaque) => None,
+                    ReturnType::Type(_, ty) => {
+                        match ty.error.as_ref().and_then(|err| err.ident()) {
+                            Some(_) => result_ok_type(&ty.ty),
+                            None => Some(ty.ty.as_ref()),
+                        }
+                    }
+                    ReturnType::Default => None,
+                };
+                sig.inputs
+                    .iter()
+                    .map(|input| input.ty.ty.as_ref())
+                    .chain(output)
+            })
+            .collect(),
+        _ => item_types(item)
+            .into_iter()
+            .map(|ty| ty.ty.as_ref())
+            .collect(),
+    }
+}
+
+/// The `T` of `Result<T, E>`
+fn result_ok_type(value: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(ty) = value else {
+        return None;
+    };
+    let syn::PathArguments::AngleBracketed(arguments) = &ty.path.segments.last()?.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
+
+/// The paths of all the named types used by the type, including the generic arguments
+fn named_types(value: &syn::Type) -> Vec<&syn::Path> {
+    match value {
+        syn::Type::Path(ty) => {
+            let args = ty
+                .path
+                .segments
+                .iter()
+                .flat_map(|segment| match &segment.arguments {
+                    syn::PathArguments::AngleBracketed(arguments) => arguments
+                        .args
+                        .iter()
+                        .filter_map(|arg| match arg {
+                            syn::GenericArgument::Type(ty) => Some(ty),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => vec![],
+                });
+            [&ty.path]
+                .into_iter()
+                .chain(args.flat_map(named_types))
+                .collect()
+        }
+        syn::Type::Reference(ty) => named_types(&ty.elem),
+        syn::Type::Array(ty) => named_types(&ty.elem),
+        syn::Type::Slice(ty) => named_types(&ty.elem),
+        syn::Type::Paren(ty) => named_types(&ty.elem),
+        syn::Type::Tuple(ty) => ty.elems.iter().flat_map(named_types).collect(),
+        _ => vec![],
+    }
+}
+
+/// The named types used as map keys must be unit-only enums defined in the module, the other
+/// named types are not serialized as strings
+fn check_map_keys(value: &syn::Type, unit_enums: &HashSet<Ident>, errors: &mut Errors) {
+    match value {
+        syn::Type::Path(ty) => {
+            for segment in &ty.path.segments {
+                let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+                    continue;
+                };
+                let args = arguments
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        syn::GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let is_map = segment.ident == "HashMap" || segment.ident == "BTreeMap";
+                if let (true, Some(syn::Type::Path(key))) = (is_map, args.first()) {
+                    let is_named = parse_type_path(key)
+                        .is_ok_and(|key| matches!(key.ts, typescript::Type::Named(_)));
+                    let is_unit_enum = key.qself.is_none()
+                        && key
+                            .path
+                            .get_ident()
+                            .is_some_and(|key| unit_enums.contains(key));
+                    if is_named && !is_unit_enum {
+                        errors.push(unsupported_map_key(args[0]));
+                    }
+                }
+                for arg in args {
+                    check_map_keys(arg, unit_enums, errors);
+                }
+            }
+        }
+        syn::Type::Reference(ty) => check_map_keys(&ty.elem, unit_enums, errors),
+        syn::Type::Array(ty) => check_map_keys(&ty.elem, unit_enums, errors),
+        syn::Type::Slice(ty) => check_map_keys(&ty.elem, unit_enums, errors),
+        syn::Type::Paren(ty) => check_map_keys(&ty.elem, unit_enums, errors),
+        syn::Type::Tuple(ty) => {
+            for elem in &ty.elems {
+                check_map_keys(elem, unit_enums, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// An array of values encoded as JSON, e.g. `Vec<T>` or `HashSet<T>`
+fn parse_json_array(value: &syn::GenericArgument) -> syn::Result<(TypeKind, typescript::Type)> {
+    let syn::GenericArgument::Type(ty) = value else {
+        return Err(syn::Error::new_spanned(value, "unsupported type"));
+    };
+    let elem = Type::try_from(ty).and_then(json_type)?;
+    Ok((TypeKind::Json, elem.ts.array()))
+}
+
+fn parse_set_type(value: &syn::PathSegment) -> syn::Result<(TypeKind, typescript::Type)> {
+    assert!(value.ident == "HashSet" || value.ident == "BTreeSet");
+
+    let arguments = &value.arguments;
+    if let syn::PathArguments::AngleBracketed(arguments) = arguments {
+        let args = &arguments.args;
+        if args.len() == 1 {
+            // HashSet<T> (where T is a valid type), encoded as an array
+            return parse_json_array(&args[0]);
+        }
+    }
+    Err(syn::Error::new_spanned(value, "unsupported type"))
+}
+
+/// Accumulator of errors, the items are parsed even after an error so that all the errors are
+/// reported at once
+#[derive(Default)]
+struct Errors(Option<syn::Error>);
+
+impl Errors {
+    fn push(&mut self, err: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(err),
+            None => self.0 = Some(err),
+        }
+    }
+
+    /// The value of the result, the error is stored otherwise
+    fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.push(err);
+                None
+            }
+        }
+    }
+
+    /// The value of the result if there are no errors, all the errors combined otherwise
+    fn finish<T>(self, result: syn::Result<T>) -> syn::Result<T> {
+        match (self.0, result) {
+            (None, result) => result,
+            (Some(mut errors), Err(err)) => {
+                errors.combine(err);
+                Err(errors)
+            }
+            (Some(errors), Ok(_)) => Err(errors),
+        }
+    }
+}
+
+/// Both values, or the errors of both results combined
+fn join<A, B>(a: syn::Result<A>, b: syn::Result<B>) -> syn::Result<(A, B)> {
+    match (a, b) {
+        (Ok(a), Ok(b)) => Ok((a, b)),
+        (Err(mut err), Err(other)) => {
+            err.combine(other);
+            Err(err)
+        }
+        (Err(err), Ok(_)) | (Ok(_), Err(err)) => Err(err),
+    }
+}
+
+/// Collect all the values, or the errors of all the results combined
+fn collect_all<T, C: FromIterator<T>>(
+    iter: impl IntoIterator<Item = syn::Result<T>>,
+) -> syn::Result<C> {
+    let mut errors = Errors::default();
+    let values = iter
+        .into_iter()
+        .filter_map(|result| errors.check(result))
+        .collect();
+    errors.finish(Ok(values))
+}
+
 fn visibility_pub(vis: &Visibility, span: Span) -> Token![pub] {
     Token![pub](match vis {
         Visibility::Public(vis) => vis.span(),
@@ -556,3 +1912,401 @@ fn visibility_pub(vis: &Visibility, span: Span) -> Token![pub] {
         Visibility::Inherited => span,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(module: syn::ItemMod) -> Vec<String> {
+        match parse_module(module) {
+            Ok(_) => vec![],
+            Err(err) => err.into_iter().map(|err| err.to_string()).collect(),
+        }
+    }
+
+    /// The TypeScript type of the first argument of the first function
+    fn argument_type(module: syn::ItemMod) -> String {
+        let module = parse_module(module).unwrap();
+        match &module.items[..] {
+            [.., Item::Fn(item)] => item.sig.inputs[0].ty.ts.to_string(),
+            _ => panic!("expected a function"),
+        }
+    }
+
+    #[test]
+    fn every_bad_item_is_reported() {
+        let module = parse_quote! {
+            mod ffi {
+                pub fn first(data: &Vec<u8>) {}
+
+                pub fn valid(value: u32) -> u32 {
+                    value
+                }
+
+                pub fn second(name: &String) {}
+            }
+        };
+        assert_eq!(
+            errors(module),
+            [
+                "unsupported type, use `&[u8]` or `Vec<u8>` instead of `&Vec<u8>`",
+                "unsupported type, use `&str` or `String` instead of `&String`",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_bad_argument_is_reported() {
+        let module = parse_quote! {
+            mod ffi {
+                pub fn function<T>(value: T, point: &Point, (a, b): (u8, u8)) {}
+            }
+        };
+        assert_eq!(
+            errors(module),
+            [
+                "function parameters are not supported",
+                "unsupported type, use `Point` instead of `&Point`",
+                "pattern matching is not supported",
+            ]
+        );
+    }
+
+    #[test]
+    fn suggestions() {
+        let cases: [(syn::Type, &str); 6] = [
+            (
+                parse_quote!(&mut Vec<i32>),
+                "use `&mut [i32]` or `Vec<i32>`",
+            ),
+            (parse_quote!(&mut str), "use `String`"),
+            (parse_quote!(&[Point]), "use `Vec<Point>`"),
+            (parse_quote!(Box<Vec<u8>>), "use `Vec<u8>`"),
+            (parse_quote!(&mut String), "use `String`"),
+            (parse_quote!(*const f32), "use `&[f32]` or `f32`"),
+        ];
+        for (ty, suggestion) in cases {
+            let module = parse_quote! {
+                mod ffi {
+                    pub fn function(value: #ty) {}
+                }
+            };
+            let expected = format!(
+                "unsupported type, {} instead of `{}`",
+                suggestion,
+                type_name(&ty)
+            );
+            assert_eq!(errors(module), [expected]);
+        }
+    }
+
+    #[test]
+    fn map_keys() {
+        let module = parse_quote! {
+            mod ffi {
+                pub struct Point {
+                    pub x: f64,
+                    pub y: f64,
+                }
+
+                pub enum Shape {
+                    Circle(f64),
+                }
+
+                pub enum Color {
+                    Red,
+                    Green,
+                }
+
+                pub fn points(value: HashMap<Point, u8>) {}
+
+                pub fn shapes(value: Option<BTreeMap<Shape, u8>>) {}
+
+                pub fn colors(value: HashMap<Color, Vec<u8>>) {}
+            }
+        };
+        assert_eq!(
+            errors(module),
+            [
+                "unsupported map key, use `String`, an integer or a unit-only enum instead of `Point`",
+                "unsupported map key, use `String`, an integer or a unit-only enum instead of `Shape`",
+            ]
+        );
+    }
+
+    #[test]
+    fn map_values_and_set_elements() {
+        let module = parse_quote! {
+            mod ffi {
+                pub fn function(value: HashMap<String, Vec<u8>>) {}
+            }
+        };
+        assert_eq!(argument_type(module), "Record<string, number[]>");
+
+        let module = parse_quote! {
+            mod ffi {
+                pub fn function(value: HashSet<[u8; 4]>) {}
+            }
+        };
+        assert_eq!(argument_type(module), "number[][]");
+
+        let module = parse_quote! {
+            mod ffi {
+                pub fn function(value: HashMap<String, &Vec<u8>>) {}
+            }
+        };
+        assert_eq!(
+            errors(module),
+            ["unsupported type, use `&[u8]` or `Vec<u8>` instead of `&Vec<u8>`"]
+        );
+    }
+
+    #[test]
+    fn error_types_are_not_values() {
+        let module = parse_quote! {
+            mod ffi {
+                pub enum ParseError {
+                    Empty,
+                }
+
+                pub struct Report {
+                    pub errors: Vec<ParseError>,
+                }
+
+                pub fn parse(value: &str) -> Result<u32, ParseError> {
+                    unreachable!()
+                }
+
+                pub fn retry(error: ParseError) -> Option<ParseError> {
+                    unreachable!()
+                }
+            }
+        };
+        let message = "`ParseError` is thrown as an error class, it can not be used as a value";
+        assert_eq!(errors(module), [message, message, message]);
+    }
+
+    #[test]
+    fn opaque_handles_are_only_returned_as_self() {
+        let module = parse_quote! {
+            mod ffi {
+                pub struct Counter {
+                    value: u32,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        unreachable!()
+                    }
+
+                    pub fn fork(&self) -> Counter {
+                        unreachable!()
+                    }
+
+                    pub fn parse(value: &str) -> Result<Self, String> {
+                        unreachable!()
+                    }
+                }
+
+                pub fn counter() -> Option<Counter> {
+                    unreachable!()
+                }
+            }
+        };
+        assert_eq!(
+            errors(module),
+            [
+                "`Counter` is an opaque handle, it can only be returned as `Self` by its methods",
+                "`Counter` is an opaque handle, it can only be returned as `Self` by its methods",
+            ]
+        );
+    }
+
+    #[test]
+    fn opaque_handles_and_callbacks_are_not_passed_back() {
+        let module = parse_quote! {
+            mod ffi {
+                pub struct Counter {
+                    value: u32,
+                }
+
+                impl Counter {
+                    pub fn merge(&mut self, other: Self) {}
+                }
+
+                pub fn read(counter: Counter) -> u32 {
+                    unreachable!()
+                }
+
+                pub fn callback() -> impl Fn(u32) {
+                    unreachable!()
+                }
+            }
+        };
+        assert_eq!(
+            errors(module),
+            [
+                "callbacks are supported only as arguments",
+                "`Counter` is an opaque handle, it can only be returned as `Self` by its methods",
+                "`Counter` is an opaque handle, it can only be returned as `Self` by its methods",
+            ]
+        );
+    }
+
+    #[test]
+    fn large_integers_are_not_json_values() {
+        let module = parse_quote! {
+            mod ffi {
+                pub struct Balance {
+                    pub amount: u128,
+                }
+
+                pub fn total(values: Vec<i128>) -> Option<u128> {
+                    unreachable!()
+                }
+
+                pub fn checked(value: u128) -> Result<(i128, u8), String> {
+                    unreachable!()
+                }
+
+                pub fn balances() -> HashMap<u128, u32> {
+                    unreachable!()
+                }
+            }
+        };
+        let message =
+            "unsupported type, 128-bit integers are supported only as arguments and results";
+        assert_eq!(errors(module), [message, message, message, message]);
+
+        let module = parse_quote! {
+            mod ffi {
+                pub fn wide_abs(x: i128) -> u128 {
+                    unreachable!()
+                }
+            }
+        };
+        assert_eq!(argument_type(module), "bigint");
+    }
+
+    #[test]
+    fn conditional_fields_and_variants() {
+        let module = parse_quote! {
+            mod ffi {
+                pub struct Point {
+                    pub x: f64,
+                    #[cfg(feature = "z")]
+                    pub z: f64,
+                }
+
+                #[cfg_attr(feature = "camel", serde(rename_all = "camelCase"))]
+                pub enum Shape {
+                    #[cfg_attr(feature = "z", serde(rename = "sphere"))]
+                    Circle { radius: f64 },
+                    #[cfg(feature = "z")]
+                    Cube(f64),
+                }
+
+                #[cfg(feature = "z")]
+                pub struct Depth(#[cfg(feature = "z")] f64);
+
+                #[cfg_attr(feature = "debug", derive(Debug))]
+                pub struct Size {
+                    pub width: f64,
+                }
+            }
+        };
+        let message = "conditional attributes are supported only on items and methods";
+        assert_eq!(
+            errors(module),
+            [
+                message,
+                "`serde` attributes can not be conditional",
+                message,
+                message,
+                message,
+            ]
+        );
+    }
+
+    #[test]
+    fn tagged_enums() {
+        let module = parse_quote! {
+            mod ffi {
+                #[serde(tag = "type")]
+                pub enum Event {
+                    KeyDown { key: String },
+                    Resize(u32, u32),
+                    Idle,
+                }
+
+                #[serde(tag = "t", content = "c")]
+                pub enum Value {
+                    Number(f64),
+                    Pair(f64, f64),
+                }
+
+                #[serde(content = "c")]
+                pub enum Content {
+                    Number(f64),
+                }
+
+                #[serde(untagged)]
+                pub enum Untagged {
+                    Number(f64),
+                }
+
+                #[serde(tag = "type")]
+                pub struct Point {
+                    pub x: f64,
+                }
+            }
+        };
+        assert_eq!(
+            errors(module),
+            [
+                "internally tagged enums can not have tuple variants, add `content` to store the fields apart",
+                "`content` requires `tag`",
+                "untagged enums are not supported",
+                "`tag` and `content` are supported only by enums",
+            ]
+        );
+    }
+
+    #[test]
+    fn float_arrays() {
+        let module = parse_module(parse_quote! {
+            mod ffi {
+                pub struct Samples {
+                    pub values: Vec<f64>,
+                    pub origin: [f32; 3],
+                }
+
+                pub fn sum(values: Vec<f64>, weights: Box<[f32]>) -> f64 {
+                    unreachable!()
+                }
+            }
+        })
+        .unwrap();
+        let [Item::Struct(samples), Item::Fn(sum)] = &module.items[..] else {
+            panic!("expected a struct and a function");
+        };
+
+        let fields = samples
+            .fields
+            .iter()
+            .map(|field| field.ty.ts.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["number[]", "number[]"]);
+        assert!(samples.native_fields().is_none());
+
+        let inputs = &sum.sig.inputs;
+        assert!(matches!(
+            inputs[0].ty.kind,
+            TypeKind::BufferOwned(TypeNative::F64)
+        ));
+        assert!(matches!(
+            inputs[1].ty.kind,
+            TypeKind::BufferOwned(TypeNative::F32)
+        ));
+    }
+}
@@ -0,0 +1,324 @@
+//! The `serde` attributes changing the JSON representation of the types.
+
+use syn::{meta::ParseNestedMeta, Attribute, LitStr};
+
+/// The attributes which change the representation in a way that can not be described by the
+/// bindings
+const UNSUPPORTED: &[&str] = &[
+    "transparent",
+    "remote",
+    "into",
+    "from",
+    "try_from",
+    "with",
+    "serialize_with",
+    "deserialize_with",
+    "rename_all_fields",
+    "variant_identifier",
+    "field_identifier",
+];
+
+/// The `serde` attributes of a container, a variant or a field
+#[derive(Default)]
+pub struct Serde {
+    /// `rename = ".."`
+    pub rename: Option<String>,
+    /// `rename_all = ".."`
+    pub rename_all: Option<RenameRule>,
+    /// `skip`, the field is never serialized or deserialized
+    pub skip: bool,
+    /// `default`, `skip_serializing`, `skip_serializing_if = ".."` or `skip_deserializing`, the
+    /// field can be missing
+    pub optional: bool,
+    /// `flatten`
+    pub flatten: bool,
+    /// `tag = ".."`, the name of the property of the variant name (internally tagged enums)
+    pub tag: Option<String>,
+    /// `content = ".."`, the name of the property of the variant fields (adjacently tagged enums)
+    pub content: Option<String>,
+}
+
+impl Serde {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut serde = Serde::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                if let Some(name) = UNSUPPORTED.iter().find(|name| path.is_ident(name)) {
+                    return Err(meta.error(format!(
+                        "`{}` changes the JSON representation, it is not supported",
+                        name
+                    )));
+                } else if path.is_ident("rename") {
+                    let rename = parse_rename(&meta)?;
+                    serde.rename = Some(rename.value());
+                } else if path.is_ident("rename_all") {
+                    let rename_all = parse_rename(&meta)?;
+                    let rule = RenameRule::from_str(&rename_all.value())
+                        .ok_or_else(|| syn::Error::new_spanned(&rename_all, "unknown case"))?;
+                    serde.rename_all = Some(rule);
+                } else if path.is_ident("skip") {
+                    serde.skip = true;
+                } else if path.is_ident("default") {
+                    if meta.input.peek(syn::Token![=]) {
+                        meta.value()?.parse::<LitStr>()?;
+                    }
+                    serde.optional = true;
+                } else if path.is_ident("skip_serializing_if") {
+                    meta.value()?.parse::<LitStr>()?;
+                    serde.optional = true;
+                } else if path.is_ident("skip_serializing") || path.is_ident("skip_deserializing") {
+                    serde.optional = true;
+                } else if path.is_ident("flatten") {
+                    serde.flatten = true;
+                } else if path.is_ident("tag") {
+                    serde.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("content") {
+                    serde.content = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if path.is_ident("untagged") {
+                    return Err(meta.error("untagged enums are not supported"));
+                } else if meta.input.peek(syn::Token![=]) {
+                    // other attributes do not change the representation
+                    meta.value()?.parse::<syn::Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    // e.g. `bound(serialize = "..")`
+                    meta.parse_nested_meta(|nested| {
+                        if nested.input.peek(syn::Token![=]) {
+                            nested.value()?.parse::<syn::Expr>()?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(serde)
+    }
+}
+
+/// The value of `rename = ".."`, or of `rename(serialize = "..", deserialize = "..")` when both
+/// values are the same: the bindings serialize and deserialize the same types
+fn parse_rename(meta: &ParseNestedMeta) -> syn::Result<LitStr> {
+    if meta.input.peek(syn::Token![=]) {
+        return meta.value()?.parse::<LitStr>();
+    }
+
+    let mut serialize = None;
+    let mut deserialize = None;
+    meta.parse_nested_meta(|nested| {
+        if nested.path.is_ident("serialize") {
+            serialize = Some(nested.value()?.parse::<LitStr>()?);
+        } else if nested.path.is_ident("deserialize") {
+            deserialize = Some(nested.value()?.parse::<LitStr>()?);
+        } else {
+            return Err(nested.error("expected `serialize` or `deserialize`"));
+        }
+        Ok(())
+    })?;
+    match (serialize, deserialize) {
+        (Some(serialize), Some(deserialize)) if serialize.value() == deserialize.value() => {
+            Ok(serialize)
+        }
+        _ => {
+            Err(meta
+                .error("different names for serialization and deserialization are not supported"))
+        }
+    }
+}
+
+/// The case conversion applied by `rename_all`
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    LowerCase,
+    UpperCase,
+    PascalCase,
+    CamelCase,
+    SnakeCase,
+    ScreamingSnakeCase,
+    KebabCase,
+    ScreamingKebabCase,
+}
+
+impl RenameRule {
+    fn from_str(rule: &str) -> Option<Self> {
+        match rule {
+            "lowercase" => Some(Self::LowerCase),
+            "UPPERCASE" => Some(Self::UpperCase),
+            "PascalCase" => Some(Self::PascalCase),
+            "camelCase" => Some(Self::CamelCase),
+            "snake_case" => Some(Self::SnakeCase),
+            "SCREAMING_SNAKE_CASE" => Some(Self::ScreamingSnakeCase),
+            "kebab-case" => Some(Self::KebabCase),
+            "SCREAMING-KEBAB-CASE" => Some(Self::ScreamingKebabCase),
+            _ => None,
+        }
+    }
+
+    /// Apply the rule to a variant name, written in `PascalCase`
+    pub fn apply_to_variant(&self, variant: &str) -> String {
+        match self {
+            Self::PascalCase => variant.to_owned(),
+            Self::LowerCase => variant.to_ascii_lowercase(),
+            Self::UpperCase => variant.to_ascii_uppercase(),
+            Self::CamelCase => lowercase_first(variant),
+            Self::SnakeCase => {
+                let mut snake = String::new();
+                for (index, ch) in variant.char_indices() {
+                    if index > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            Self::ScreamingSnakeCase => Self::SnakeCase
+                .apply_to_variant(variant)
+                .to_ascii_uppercase(),
+            Self::KebabCase => Self::SnakeCase.apply_to_variant(variant).replace('_', "-"),
+            Self::ScreamingKebabCase => Self::ScreamingSnakeCase
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+
+    /// Apply the rule to a field name, written in `snake_case`
+    pub fn apply_to_field(&self, field: &str) -> String {
+        match self {
+            Self::LowerCase | Self::SnakeCase => field.to_owned(),
+            Self::UpperCase => field.to_ascii_uppercase(),
+            Self::PascalCase => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            Self::CamelCase => lowercase_first(&Self::PascalCase.apply_to_field(field)),
+            Self::ScreamingSnakeCase => field.to_ascii_uppercase(),
+            Self::KebabCase => field.replace('_', "-"),
+            Self::ScreamingKebabCase => Self::ScreamingSnakeCase
+                .apply_to_field(field)
+                .replace('_', "-"),
+        }
+    }
+}
+
+fn lowercase_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    // the expected values are the ones of serde
+    #[test]
+    fn rename_variants() {
+        let variants = ["Outcome", "VeryTasty", "A", "Z42"];
+        let cases = [
+            (RenameRule::LowerCase, ["outcome", "verytasty", "a", "z42"]),
+            (RenameRule::UpperCase, ["OUTCOME", "VERYTASTY", "A", "Z42"]),
+            (RenameRule::PascalCase, ["Outcome", "VeryTasty", "A", "Z42"]),
+            (RenameRule::CamelCase, ["outcome", "veryTasty", "a", "z42"]),
+            (RenameRule::SnakeCase, ["outcome", "very_tasty", "a", "z42"]),
+            (
+                RenameRule::ScreamingSnakeCase,
+                ["OUTCOME", "VERY_TASTY", "A", "Z42"],
+            ),
+            (RenameRule::KebabCase, ["outcome", "very-tasty", "a", "z42"]),
+            (
+                RenameRule::ScreamingKebabCase,
+                ["OUTCOME", "VERY-TASTY", "A", "Z42"],
+            ),
+        ];
+        for (rule, expected) in cases {
+            for (variant, expected) in variants.iter().zip(expected) {
+                assert_eq!(rule.apply_to_variant(variant), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rename_fields() {
+        let fields = ["outcome", "very_tasty", "a", "z42"];
+        let cases = [
+            (RenameRule::LowerCase, ["outcome", "very_tasty", "a", "z42"]),
+            (RenameRule::UpperCase, ["OUTCOME", "VERY_TASTY", "A", "Z42"]),
+            (RenameRule::PascalCase, ["Outcome", "VeryTasty", "A", "Z42"]),
+            (RenameRule::CamelCase, ["outcome", "veryTasty", "a", "z42"]),
+            (RenameRule::SnakeCase, ["outcome", "very_tasty", "a", "z42"]),
+            (
+                RenameRule::ScreamingSnakeCase,
+                ["OUTCOME", "VERY_TASTY", "A", "Z42"],
+            ),
+            (RenameRule::KebabCase, ["outcome", "very-tasty", "a", "z42"]),
+            (
+                RenameRule::ScreamingKebabCase,
+                ["OUTCOME", "VERY-TASTY", "A", "Z42"],
+            ),
+        ];
+        for (rule, expected) in cases {
+            for (field, expected) in fields.iter().zip(expected) {
+                assert_eq!(rule.apply_to_field(field), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rename_with_the_same_name_in_both_directions() {
+        let attrs: Vec<Attribute> = vec![
+            parse_quote!(#[serde(rename(serialize = "b", deserialize = "b"))]),
+            parse_quote!(#[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]),
+        ];
+        let serde = Serde::parse(&attrs).unwrap();
+        assert_eq!(serde.rename.as_deref(), Some("b"));
+        assert!(matches!(serde.rename_all, Some(RenameRule::CamelCase)));
+    }
+
+    #[test]
+    fn rename_with_different_names_is_rejected() {
+        let attrs: Vec<Attribute> = vec![parse_quote!(#[serde(rename(serialize = "a"))])];
+        let error = Serde::parse(&attrs).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "different names for serialization and deserialization are not supported"
+        );
+    }
+
+    #[test]
+    fn attributes_changing_the_representation_are_rejected() {
+        let attrs: [Attribute; 3] = [
+            parse_quote!(#[serde(transparent)]),
+            parse_quote!(#[serde(with = "module")]),
+            parse_quote!(#[serde(try_from = "String")]),
+        ];
+        for attr in attrs {
+            let error = Serde::parse(&[attr]).err().unwrap();
+            assert!(error
+                .to_string()
+                .ends_with("changes the JSON representation, it is not supported"));
+        }
+    }
+
+    #[test]
+    fn attributes_not_changing_the_representation_are_ignored() {
+        let attrs: Vec<Attribute> = vec![parse_quote!(
+            #[serde(deny_unknown_fields, alias = "other", bound(serialize = "T: Serialize"))]
+        )];
+        assert!(Serde::parse(&attrs).is_ok());
+    }
+}
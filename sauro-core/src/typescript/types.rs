@@ -4,6 +4,8 @@ pub enum Type {
     Named(TypeNamed),
     Union(TypeUnion),
     Array(TypeArray),
+    Record(TypeRecord),
+    Tuple(TypeTuple),
 }
 
 impl Type {
@@ -12,6 +14,22 @@ impl Type {
             members: Box::new(self),
         })
     }
+
+    pub fn record(key: Self, value: Self) -> Self {
+        Self::Record(TypeRecord {
+            key: Box::new(key),
+            value: Box::new(value),
+        })
+    }
+
+    pub fn tuple(members: Vec<Self>) -> Self {
+        Self::Tuple(TypeTuple { members })
+    }
+
+    /// Check if parentheses are required when the type is combined with other types
+    pub fn is_union(&self) -> bool {
+        matches!(self, Self::Union(ty) if ty.members.len() > 1)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -32,6 +50,18 @@ pub struct TypeArray {
     pub members: Box<Type>,
 }
 
+/// An object whose property names are of type `key`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TypeRecord {
+    pub key: Box<Type>,
+    pub value: Box<Type>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TypeTuple {
+    pub members: Vec<Type>,
+}
+
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -39,6 +69,8 @@ impl std::fmt::Display for Type {
             Self::Named(ty) => write!(f, "{}", ty),
             Self::Union(ty) => write!(f, "{}", ty),
             Self::Array(ty) => write!(f, "{}", ty),
+            Self::Record(ty) => write!(f, "{}", ty),
+            Self::Tuple(ty) => write!(f, "{}", ty),
         }
     }
 }
@@ -79,6 +111,25 @@ impl std::fmt::Display for TypeArray {
     }
 }
 
+impl std::fmt::Display for TypeRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Record<{}, {}>", self.key, self.value)
+    }
+}
+
+impl std::fmt::Display for TypeTuple {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (index, member) in self.members.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", member)?;
+        }
+        write!(f, "]")
+    }
+}
+
 impl std::ops::BitOr for Type {
     type Output = Self;
 
@@ -112,6 +163,8 @@ macro_rules! primitive_types {
 primitive_types! {
     null,
     undefined,
+    void,
+    boolean,
     number,
     bigint,
     string,
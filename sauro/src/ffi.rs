@@ -0,0 +1,367 @@
+//! Support functions used by the code generated by [`bindgen`](crate::bindgen).
+
+/// Copy the data in a new buffer prefixed by its length (encoded as big endian 32 bits integer).
+///
+/// The returned buffer must be released using [`sauro_dealloc`].
+pub fn len_prefixed(data: &[u8]) -> *const u8 {
+    let len = u32::try_from(data.len()).expect("buffer is too large");
+
+    let mut buffer = Vec::with_capacity(4 + data.len());
+    buffer.extend_from_slice(&len.to_be_bytes());
+    buffer.extend_from_slice(data);
+
+    Box::into_raw(buffer.into_boxed_slice()) as *const u8
+}
+
+/// Release a buffer created by [`len_prefixed`].
+///
+/// # Safety
+///
+/// The pointer must be null or obtained from [`len_prefixed`], and it must not be used after this
+/// call.
+#[no_mangle]
+pub unsafe extern "C" fn sauro_dealloc(ptr: *mut u8) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let len = u32::from_be_bytes(unsafe { ptr.cast::<[u8; 4]>().read() }) as usize;
+    let buffer = std::ptr::slice_from_raw_parts_mut(ptr, 4 + len);
+    drop(unsafe { Box::from_raw(buffer) });
+}
+
+/// Failure reported to the caller of a binding.
+pub enum Error {
+    /// The binding panicked
+    Panic(String),
+    /// The arguments can not be decoded
+    Decode(String),
+}
+
+impl Error {
+    pub fn decode(err: impl std::fmt::Display) -> Self {
+        Self::Decode(err.to_string())
+    }
+
+    fn panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_owned()
+        };
+        Self::Panic(message)
+    }
+
+    /// Encode the error as a JSON object with `kind` and `message` properties.
+    fn encode(&self) -> *const u8 {
+        let (kind, message) = match self {
+            Error::Panic(message) => ("panic", message),
+            Error::Decode(message) => ("decode", message),
+        };
+        let json = serde_json::json!({ "kind": kind, "message": message });
+        len_prefixed(json.to_string().as_bytes())
+    }
+}
+
+/// Value returned by a binding when it fails, it is ignored by the caller.
+pub trait Fallback {
+    fn fallback() -> Self;
+}
+
+macro_rules! impl_fallback {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Fallback for $ty {
+                fn fallback() -> Self {
+                    Default::default()
+                }
+            }
+        )*
+    };
+}
+
+impl_fallback!(
+    (),
+    bool,
+    i8,
+    i16,
+    i32,
+    i64,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    f32,
+    f64
+);
+
+impl<T> Fallback for *const T {
+    fn fallback() -> Self {
+        std::ptr::null()
+    }
+}
+
+impl<T> Fallback for *mut T {
+    fn fallback() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+/// Call the binding implementation, catching panics and errors.
+///
+/// When the binding fails, the encoded error is written in `error` (it must be released using
+/// [`sauro_dealloc`]) and a fallback value is returned.
+///
+/// # Safety
+///
+/// The pointer `error` must be null or valid for writes.
+pub unsafe fn call<R, F>(error: *mut *const u8, f: F) -> R
+where
+    R: Fallback,
+    F: FnOnce() -> Result<R, Error>,
+{
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(Error::panic(payload)));
+
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            if !error.is_null() {
+                unsafe { error.write(err.encode()) };
+            }
+            R::fallback()
+        }
+    }
+}
+
+/// Wrapper used to get the message of an error, when it implements [`Display`](std::fmt::Display).
+///
+/// The message is obtained using autoref specialization, both [`DisplayErrorMessage`] and
+/// [`NoErrorMessage`] must be in scope and the method must be called as
+/// `(&ErrorMessage(&err)).message()`.
+pub struct ErrorMessage<'a, T>(pub &'a T);
+
+pub trait DisplayErrorMessage {
+    fn message(&self) -> Option<String>;
+}
+
+impl<T: std::fmt::Display> DisplayErrorMessage for ErrorMessage<'_, T> {
+    fn message(&self) -> Option<String> {
+        Some(self.0.to_string())
+    }
+}
+
+pub trait NoErrorMessage {
+    fn message(&self) -> Option<String>;
+}
+
+impl<T> NoErrorMessage for &ErrorMessage<'_, T> {
+    fn message(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Encode the error of a `Result` together with its message.
+pub fn encode_result_err<E: serde::Serialize>(
+    err: &E,
+    message: Option<String>,
+) -> serde_json::Result<String> {
+    let json = serde_json::json!({ "Err": err, "message": message });
+    serde_json::to_string(&json)
+}
+
+/// Size of the metadata record containing the given parts.
+pub const fn metadata_len(parts: &[&str]) -> usize {
+    let mut len = 4;
+    let mut index = 0;
+    while index < parts.len() {
+        len += 4 + parts[index].len();
+        index += 1;
+    }
+    len
+}
+
+/// Build the metadata record embedded in the library, read by the CLI to generate the bindings.
+///
+/// The record is prefixed by the length of its content, each part is a string prefixed by its
+/// length (all lengths are encoded as big endian 32 bits integers).
+pub const fn metadata<const N: usize>(parts: &[&str]) -> [u8; N] {
+    assert!(metadata_len(parts) == N, "metadata size mismatch");
+
+    let mut buffer = [0; N];
+    let mut offset = write_metadata_len(&mut buffer, 0, N - 4);
+    let mut index = 0;
+    while index < parts.len() {
+        let part = parts[index].as_bytes();
+        offset = write_metadata_len(&mut buffer, offset, part.len());
+
+        let mut byte = 0;
+        while byte < part.len() {
+            buffer[offset + byte] = part[byte];
+            byte += 1;
+        }
+        offset += part.len();
+        index += 1;
+    }
+    buffer
+}
+
+const fn write_metadata_len(buffer: &mut [u8], offset: usize, len: usize) -> usize {
+    let bytes = (len as u32).to_be_bytes();
+    let mut index = 0;
+    while index < 4 {
+        buffer[offset + index] = bytes[index];
+        index += 1;
+    }
+    offset + 4
+}
+
+/// Add the hash of an item to the fingerprint of a module, i.e. a 64 bits FNV-1a step over the
+/// little endian bytes of the item hash.
+///
+/// It is evaluated by the function exported by [`bindgen`](crate::bindgen), it must be equal to
+/// `sauro_core::abi::combine` used to compute the fingerprint expected by the bindings.
+pub const fn abi_combine(hash: u64, item_hash: u64) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+
+    let bytes = item_hash.to_le_bytes();
+    let mut hash = hash;
+    let mut index = 0;
+    while index < bytes.len() {
+        hash = (hash ^ bytes[index] as u64).wrapping_mul(PRIME);
+        index += 1;
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abi_combine_is_equal_to_the_fingerprint_of_the_bindings() {
+        let hashes = [0, 1, 0xff, 0x0123456789abcdef, u64::MAX];
+        for item_hash in hashes {
+            let mut expected = sauro_core::abi::init();
+            let mut hash = sauro_core::abi::init();
+            for _ in 0..3 {
+                expected = sauro_core::abi::combine(expected, item_hash);
+                hash = abi_combine(hash, item_hash);
+                assert_eq!(hash, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn layout_of_the_bindings_is_the_compiled_layout() {
+        use sauro_core::syntax::{Layout, TypeNative};
+        use std::mem::{align_of, offset_of, size_of};
+
+        #[repr(C)]
+        struct Point {
+            x: f64,
+            y: f64,
+            id: u8,
+        }
+        let layout = Layout::new(&[TypeNative::F64, TypeNative::F64, TypeNative::U8]);
+        assert_eq!(layout.offsets, [0, 8, 16]);
+        assert_eq!(
+            layout,
+            Layout {
+                offsets: vec![
+                    offset_of!(Point, x),
+                    offset_of!(Point, y),
+                    offset_of!(Point, id)
+                ],
+                size: size_of::<Point>(),
+                align: align_of::<Point>(),
+            }
+        );
+
+        #[repr(C)]
+        struct Mixed(u8, u16, bool, u32, i8, u64, f32);
+        let layout = Layout::new(&[
+            TypeNative::U8,
+            TypeNative::U16,
+            TypeNative::Bool,
+            TypeNative::U32,
+            TypeNative::I8,
+            TypeNative::U64,
+            TypeNative::F32,
+        ]);
+        assert_eq!(
+            layout,
+            Layout {
+                offsets: vec![
+                    offset_of!(Mixed, 0),
+                    offset_of!(Mixed, 1),
+                    offset_of!(Mixed, 2),
+                    offset_of!(Mixed, 3),
+                    offset_of!(Mixed, 4),
+                    offset_of!(Mixed, 5),
+                    offset_of!(Mixed, 6),
+                ],
+                size: size_of::<Mixed>(),
+                align: align_of::<Mixed>(),
+            }
+        );
+    }
+
+    #[test]
+    fn large_integers_are_passed_exactly() {
+        // the integers above 2^53 can not be represented by a JavaScript number
+        let values = [(1i128 << 53) + 1, i128::MAX, i128::MIN, -(1i128 << 100) - 1];
+        for value in values {
+            let ptr = len_prefixed(&value.to_le_bytes()).cast_mut();
+            let buffer = unsafe { std::slice::from_raw_parts(ptr, 4 + 16) };
+            assert_eq!(buffer[..4], 16u32.to_be_bytes());
+            let decoded = i128::from_le_bytes(buffer[4..].try_into().unwrap());
+            unsafe { sauro_dealloc(ptr) };
+            assert_eq!(decoded, value);
+        }
+    }
+
+    #[test]
+    // the method is called as in the generated code, the borrow selects the implementation
+    #[allow(clippy::needless_borrow)]
+    fn result_errors_carry_the_display_message() {
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        enum ParseError {
+            Invalid { position: u32 },
+        }
+
+        impl std::fmt::Display for ParseError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    ParseError::Invalid { position } => write!(f, "invalid at {}", position),
+                }
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Code(u32);
+
+        let err = ParseError::Invalid { position: 3 };
+        let message = (&ErrorMessage(&err)).message();
+        assert_eq!(message.as_deref(), Some("invalid at 3"));
+        assert_eq!(
+            encode_result_err(&err, message).unwrap(),
+            r#"{"Err":{"Invalid":{"position":3}},"message":"invalid at 3"}"#
+        );
+
+        // the errors without `Display` have no message, the bindings show the JSON value
+        let err = Code(7);
+        let message = (&ErrorMessage(&err)).message();
+        assert_eq!(message, None);
+        assert_eq!(
+            encode_result_err(&err, message).unwrap(),
+            r#"{"Err":7,"message":null}"#
+        );
+    }
+}
@@ -1,3 +1,7 @@
+#[doc(hidden)]
+pub mod ffi;
+pub mod runtime;
+
 #[doc(hidden)]
 pub use ::serde;
 
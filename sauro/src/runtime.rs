@@ -0,0 +1,167 @@
+//! Runtime used to drive the `async` functions exported by [`bindgen`](crate::bindgen).
+//!
+//! Asynchronous bindings are exported as non blocking symbols: Deno calls them on a separate
+//! thread, that is blocked until the future completes. By default the future is polled by a
+//! minimal built-in executor, that is enough for futures that do not depend on a specific runtime.
+//! Futures that require a specific runtime (e.g. tokio) need a custom [`Executor`], registered
+//! using [`set_executor`] before any asynchronous binding is called.
+
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    sync::{Arc, OnceLock},
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+/// An executor able to run a future to completion, blocking the current thread.
+pub trait Executor: Send + Sync + 'static {
+    fn block_on(&self, future: Pin<Box<dyn Future<Output = ()> + '_>>);
+}
+
+static EXECUTOR: OnceLock<Box<dyn Executor>> = OnceLock::new();
+
+/// Register the executor used by the asynchronous bindings.
+///
+/// The executor can be registered only once, an error is returned if an executor has been already
+/// registered.
+pub fn set_executor(executor: impl Executor) -> Result<(), SetExecutorError> {
+    EXECUTOR
+        .set(Box::new(executor))
+        .map_err(|_| SetExecutorError(()))
+}
+
+/// The error returned by [`set_executor`] if an executor has been already registered.
+#[derive(Debug)]
+pub struct SetExecutorError(());
+
+impl std::fmt::Display for SetExecutorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "an executor has been already registered")
+    }
+}
+
+impl std::error::Error for SetExecutorError {}
+
+/// Run the future to completion, using the registered executor (if any).
+#[doc(hidden)]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match EXECUTOR.get() {
+        Some(executor) => {
+            let mut output = None;
+            executor.block_on(Box::pin(async {
+                output = Some(future.await);
+            }));
+            output.expect("the executor has not completed the future")
+        }
+        None => builtin_block_on(future),
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+fn builtin_block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// The state shared by a [`Receiver`] and the thread completing it
+    #[derive(Default)]
+    struct Shared {
+        value: Option<u32>,
+        waker: Option<Waker>,
+    }
+
+    /// A future completed by another thread
+    struct Receiver(Arc<Mutex<Shared>>);
+
+    impl Future for Receiver {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            let mut shared = self.0.lock().unwrap();
+            match shared.value.take() {
+                Some(value) => Poll::Ready(value),
+                None => {
+                    shared.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    fn send_later(value: u32) -> Receiver {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let sender = Arc::clone(&shared);
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            let mut shared = sender.lock().unwrap();
+            shared.value = Some(value);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+        Receiver(shared)
+    }
+
+    #[test]
+    fn builtin_executor_is_woken_from_another_thread() {
+        assert_eq!(builtin_block_on(send_later(42)), 42);
+    }
+
+    #[test]
+    fn custom_executor() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counting;
+
+        impl Executor for Counting {
+            fn block_on(&self, future: Pin<Box<dyn Future<Output = ()> + '_>>) {
+                CALLS.fetch_add(1, Ordering::SeqCst);
+                builtin_block_on(future)
+            }
+        }
+
+        // the executor is global, it is registered only by this test
+        set_executor(Counting).unwrap();
+        assert_eq!(block_on(send_later(7)), 7);
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+
+        let err = set_executor(Counting).unwrap_err();
+        assert_eq!(err.to_string(), "an executor has been already registered");
+        assert_eq!(block_on(async { 3 }), 3);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,117 @@
+//! The bindings generated by `bindgen`, called as Deno does.
+
+#[sauro::bindgen]
+mod api {
+    pub struct Named {
+        name: String,
+    }
+
+    pub struct Counter {
+        value: i64,
+    }
+
+    impl Counter {
+        pub fn new(value: i64) -> Self {
+            assert!(value >= 0, "negative value");
+            Counter { value }
+        }
+
+        pub fn get(&self) -> i64 {
+            self.value
+        }
+    }
+
+    pub fn greet(named: Named) -> String {
+        format!("hello {}", named.name)
+    }
+
+    pub fn length(s: &str) -> u32 {
+        s.len() as u32
+    }
+
+    pub fn fail(reason: &str) -> u32 {
+        panic!("{}", reason)
+    }
+}
+
+/// Call a binding, returning its result and the decoded error (if any)
+fn call<R>(f: impl FnOnce(*mut *const u8) -> R) -> (R, Option<serde_json::Value>) {
+    let mut error = std::ptr::null();
+    let result = f(&mut error);
+    let error = (!error.is_null()).then(|| decode(error));
+    (result, error)
+}
+
+/// Copy the content of a buffer returned by a binding, releasing it
+fn buffer(ptr: *const u8) -> Vec<u8> {
+    let content = unsafe {
+        let len = u32::from_be_bytes(ptr.cast::<[u8; 4]>().read()) as usize;
+        std::slice::from_raw_parts(ptr.add(4), len).to_vec()
+    };
+    unsafe { sauro::ffi::sauro_dealloc(ptr.cast_mut()) };
+    content
+}
+
+fn decode(ptr: *const u8) -> serde_json::Value {
+    serde_json::from_slice(&buffer(ptr)).unwrap()
+}
+
+#[test]
+fn successful_calls() {
+    let s = "sauro";
+    let (len, error) = call(|error| unsafe { api::length(s.as_ptr().cast_mut(), s.len(), error) });
+    assert_eq!((len, error), (5, None));
+
+    let json = br#"{"name":"deno"}"#;
+    let (greeting, error) =
+        call(|error| unsafe { api::greet(json.as_ptr().cast_mut(), json.len(), error) });
+    assert_eq!(error, None);
+    assert_eq!(String::from_utf8(buffer(greeting)).unwrap(), "hello deno");
+
+    let (counter, error) = call(|error| unsafe { api::Counter_new(3, error) });
+    assert_eq!(error, None);
+    assert!(!counter.is_null());
+    let (value, error) = call(|error| unsafe { api::Counter_get(counter, error) });
+    assert_eq!((value, error), (3, None));
+    unsafe { api::Counter_free(counter) };
+}
+
+#[test]
+fn panics_are_reported() {
+    let reason = "out of order";
+    let (result, error) =
+        call(|error| unsafe { api::fail(reason.as_ptr().cast_mut(), reason.len(), error) });
+    assert_eq!(result, 0);
+    let error = error.unwrap();
+    assert_eq!(error["kind"], "panic");
+    assert_eq!(error["message"], "out of order");
+
+    // the pointers are null when the binding fails
+    let (counter, error) = call(|error| unsafe { api::Counter_new(-1, error) });
+    assert!(counter.is_null());
+    let error = error.unwrap();
+    assert_eq!(error["kind"], "panic");
+    assert_eq!(error["message"], "negative value");
+}
+
+#[test]
+fn invalid_arguments_are_reported() {
+    let invalid = b"\xff\xfe";
+    let (result, error) =
+        call(|error| unsafe { api::length(invalid.as_ptr().cast_mut(), invalid.len(), error) });
+    assert_eq!(result, 0);
+    let error = error.unwrap();
+    assert_eq!(error["kind"], "decode");
+    assert!(error["message"].as_str().unwrap().contains("utf-8"));
+
+    let json = br#"{"title":"deno"}"#;
+    let (greeting, error) =
+        call(|error| unsafe { api::greet(json.as_ptr().cast_mut(), json.len(), error) });
+    assert!(greeting.is_null());
+    let error = error.unwrap();
+    assert_eq!(error["kind"], "decode");
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .contains("missing field `name`"));
+}